@@ -1,16 +1,32 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 use tempdir::TempDir;
 
 use crate::Cli;
+use crate::output::Output;
+use crate::record_date::{DateFormat, Period};
+use crate::sanitizer::Sanitizer;
+use crate::volumes::Volumes;
 
 type Temp = Box<dyn AsRef<Path> + Sync + Send>;
 
+/// Processed record pdf with its number of pages
+type ProcessedRecord = Arc<Mutex<Option<(PathBuf, usize)>>>;
+
 pub struct App {
     tmp_img: Temp,
     tmp_html: Temp,
     tmp_label: Temp,
     tmp_records: Temp,
+    tmp_sources: Temp,
+    sanitizer: Sanitizer,
+    date_format: DateFormat,
+    period: Period,
+    output: Output,
+    volumes: Volumes,
+    processed_records: Mutex<HashMap<String, ProcessedRecord>>,
     cli: Cli,
 }
 
@@ -31,16 +47,71 @@ impl App {
         &self.cli
     }
 
+    pub fn sanitizer(&self) -> &Sanitizer {
+        &self.sanitizer
+    }
+
+    pub fn date_format(&self) -> &DateFormat {
+        &self.date_format
+    }
+
+    pub fn period(&self) -> &Period {
+        &self.period
+    }
+
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    pub fn volumes(&self) -> &Volumes {
+        &self.volumes
+    }
+
     pub fn new(cli: Cli) -> eyre::Result<Self> {
         Ok(Self {
             tmp_img: Self::generate_tmp("img", cli.preserve_tmp)?,
             tmp_html: Self::generate_tmp("html", cli.preserve_tmp)?,
             tmp_label: Self::generate_tmp("label", cli.preserve_tmp)?,
             tmp_records: Self::generate_tmp("records", cli.preserve_tmp)?,
+            tmp_sources: Self::generate_tmp("sources", cli.preserve_tmp)?,
+            sanitizer: Sanitizer::new(&cli.html_tags, &cli.html_styles)?,
+            date_format: DateFormat::new(&cli.date_format, &cli.month_format)?,
+            period: Period::new(cli.since.as_deref(), cli.until.as_deref())?,
+            output: Output::new(&cli)?,
+            volumes: Volumes::new(&cli)?,
+            processed_records: Mutex::default(),
             cli,
         })
     }
 
+    /// Processes the record once even if it's in documents of several people.
+    /// Other documents wait for the first one to finish the record
+    pub fn process_record_once(
+        &self,
+        record_id: String,
+        process: impl FnOnce() -> eyre::Result<(PathBuf, usize)>,
+    ) -> eyre::Result<(PathBuf, usize)> {
+        let processed = self
+            .processed_records
+            .lock()
+            .expect("Poisoned lock")
+            .entry(record_id)
+            .or_default()
+            .clone();
+
+        let mut processed = processed.lock().expect("Poisoned lock");
+
+        if let Some(processed) = processed.as_ref() {
+            return Ok(processed.clone());
+        }
+
+        let result = process()?;
+
+        *processed = Some(result.clone());
+
+        Ok(result)
+    }
+
     fn tmp_file(tmp: impl AsRef<Path>, file: impl AsRef<Path>) -> PathBuf {
         let mut tmp = tmp.as_ref().to_path_buf();
 
@@ -64,4 +135,8 @@ impl App {
     pub fn tmp_records(&self, file: impl AsRef<Path>) -> PathBuf {
         Self::tmp_file(self.tmp_records.as_ref(), file)
     }
+
+    pub fn tmp_sources(&self, file: impl AsRef<Path>) -> PathBuf {
+        Self::tmp_file(self.tmp_sources.as_ref(), file)
+    }
 }
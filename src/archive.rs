@@ -0,0 +1,225 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use eyre::eyre;
+use flate2::read::GzDecoder;
+
+use crate::app::App;
+use crate::html_export::HtmlExport;
+use crate::structs::{Export, Record};
+
+const EXTENSIONS: [&str; 3] = [".zip", ".tar.gz", ".tgz"];
+
+/// Zipped or tarred Telegram export.
+///
+/// Messages of the archive get `{archive}/{root}` as an export path, where root is
+/// the directory of `result.json` inside the archive. Referenced files are extracted
+/// later by [`Archive::extract_records`] once it's known which records are built.
+pub struct Archive;
+
+impl Archive {
+    pub fn is_archive(path: &Path) -> bool {
+        let name = path.to_string_lossy().to_lowercase();
+
+        path.is_file() && EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+    }
+
+    pub fn read(path: &Path) -> eyre::Result<Export> {
+        let mut result_json = None;
+        let mut pages = vec![];
+
+        Self::visit_entries(path, |name, entry| {
+            let (dir, file_name) = name.rsplit_once('/').unwrap_or(("", name));
+
+            if file_name == "result.json" {
+                // The shallowest one if some export contains another one
+                if result_json
+                    .as_ref()
+                    .is_none_or(|(root, _): &(String, _)| root.len() > dir.len())
+                {
+                    let mut content = String::new();
+                    entry.read_to_string(&mut content)?;
+
+                    result_json = Some((dir.to_string(), content));
+                }
+            } else if HtmlExport::is_page(file_name) {
+                let mut content = String::new();
+                entry.read_to_string(&mut content)?;
+
+                pages.push((dir.to_string(), file_name.to_string(), content));
+            }
+
+            Ok(())
+        })?;
+
+        let (root, mut export) = if let Some((root, content)) = result_json {
+            (root, serde_json::from_str::<Export>(&content)?)
+        } else {
+            let root = pages
+                .iter()
+                .map(|(dir, ..)| dir.clone())
+                .min_by_key(|dir| dir.len())
+                .ok_or_else(|| {
+                    eyre!(
+                        "{} contains neither result.json nor messages.html",
+                        path.display()
+                    )
+                })?;
+
+            let pages = pages
+                .into_iter()
+                .filter(|(dir, ..)| *dir == root)
+                .map(|(_, name, content)| (name, content))
+                .collect::<Vec<_>>();
+
+            (root, HtmlExport::from_pages(pages)?)
+        };
+
+        let export_path = path.join(root);
+
+        export
+            .messages
+            .iter_mut()
+            .for_each(|msg| msg.export_path = Some(export_path.clone()));
+
+        Ok(export)
+    }
+
+    /// Extracts files referenced by messages of the records from archives
+    /// and points messages to the extracted copies
+    pub fn extract_records<'a>(
+        app: &App,
+        records: impl IntoIterator<Item = &'a mut Record>,
+    ) -> eyre::Result<()> {
+        let mut by_export_path: HashMap<PathBuf, Vec<_>> = HashMap::new();
+
+        let messages = records
+            .into_iter()
+            .flat_map(|rec| rec.messages.iter_mut().chain(rec.addenda.iter_mut()));
+
+        for msg in messages {
+            let Some(export_path) = msg.export_path.clone() else {
+                continue;
+            };
+
+            if Self::split_export_path(&export_path).is_some() {
+                by_export_path.entry(export_path).or_default().push(msg);
+            }
+        }
+
+        for (index, (export_path, msgs)) in by_export_path.into_iter().enumerate() {
+            let (archive_path, root) =
+                Self::split_export_path(&export_path).expect("Checked above");
+
+            let files: HashSet<String> = msgs
+                .iter()
+                .flat_map(|msg| [&msg.photo, &msg.file, &msg.thumbnail])
+                .flatten()
+                .map(|file| Self::entry_name(&root, file))
+                .collect();
+
+            let dest = app.tmp_sources(index.to_string());
+
+            Self::visit_entries(&archive_path, |name, entry| {
+                if !files.contains(name) {
+                    return Ok(());
+                }
+
+                let file_path = dest.join(name.strip_prefix(&root).unwrap_or(name));
+
+                if let Some(parent) = file_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                io::copy(entry, &mut File::create(file_path)?)?;
+
+                Ok(())
+            })?;
+
+            for msg in msgs {
+                msg.export_path = Some(dest.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Files of the export inside the archive relative to its root
+    /// or `None` if the export path isn't inside an archive
+    pub fn files(export_path: &Path) -> eyre::Result<Option<HashSet<String>>> {
+        let Some((archive_path, root)) = Self::split_export_path(export_path) else {
+            return Ok(None);
+        };
+
+        let mut files = HashSet::new();
+
+        Self::visit_entries(&archive_path, |name, _| {
+            if let Some(file) = name.strip_prefix(&root) {
+                files.insert(file.to_string());
+            }
+
+            Ok(())
+        })?;
+
+        Ok(Some(files))
+    }
+
+    /// Splits `{archive}/{root}` export path into archive path and root prefix of entries
+    fn split_export_path(export_path: &Path) -> Option<(PathBuf, String)> {
+        let archive_path = export_path
+            .ancestors()
+            .find(|path| Self::is_archive(path))?;
+
+        let root = export_path
+            .strip_prefix(archive_path)
+            .ok()?
+            .to_string_lossy()
+            .to_string();
+
+        let root = if root.is_empty() { root } else { root + "/" };
+
+        Some((archive_path.to_path_buf(), root))
+    }
+
+    fn entry_name(root: &str, file: &str) -> String {
+        format!("{root}{}", file.trim_start_matches("./"))
+    }
+
+    fn visit_entries(
+        path: &Path,
+        mut visit: impl FnMut(&str, &mut dyn Read) -> eyre::Result<()>,
+    ) -> eyre::Result<()> {
+        let name = path.to_string_lossy().to_lowercase();
+
+        if name.ends_with(".zip") {
+            let mut zip = zip::ZipArchive::new(File::open(path)?)?;
+
+            for index in 0..zip.len() {
+                let mut entry = zip.by_index(index)?;
+
+                if entry.is_file() {
+                    let name = entry.name().to_string();
+
+                    visit(&name, &mut entry)?;
+                }
+            }
+        } else {
+            let mut tar = tar::Archive::new(GzDecoder::new(File::open(path)?));
+
+            for entry in tar.entries()? {
+                let mut entry = entry?;
+
+                if entry.header().entry_type().is_file() {
+                    let name = entry.path()?.to_string_lossy().to_string();
+                    let name = name.trim_start_matches("./").to_string();
+
+                    visit(&name, &mut entry)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -2,7 +2,6 @@
 import { readFileSync, writeFileSync } from "node:fs";
 import {
   PDFDocument,
-  PDFName,
   PDFFont,
   PDFString,
   PDFPage,
@@ -177,10 +176,8 @@ class App {
     });
     const linkAnnotationRef = pdfDoc.context.register(linkAnnotation);
 
-    page.node.set(
-      PDFName.of("Annots"),
-      pdfDoc.context.obj([linkAnnotationRef]),
-    );
+    // Keep links the page already has (e.g. hyperlinks of text records)
+    page.node.addAnnot(linkAnnotationRef);
   }
 
   extendPdfPages(page: PDFPage) {
@@ -1,19 +1,54 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::mem;
 
 use itertools::Itertools;
 
+use crate::overrides::Overrides;
+use crate::persons::Persons;
+use crate::report::{SkipReason, SkippedMessage};
 use crate::structs::{Export, Message, Record};
 
+/// Records of every person by the person name
+pub type PersonRecords = HashMap<String, Vec<Record>>;
+
 pub struct Categorizer {}
 
 impl Categorizer {
-    pub fn process_exports(exports: Vec<Export>) -> HashMap<String, Vec<Record>> {
-        let messages = Self::messages(exports);
+    /// Records of every person and messages that didn't get into any record
+    pub fn process_exports(
+        mut exports: Vec<Export>,
+        overrides: &Overrides,
+        persons: &Persons,
+    ) -> eyre::Result<(PersonRecords, Vec<SkippedMessage>)> {
+        // Sources without messages provide records as is
+        let records = exports
+            .iter_mut()
+            .flat_map(|export| mem::take(&mut export.records))
+            .collect_vec();
+
+        // Messages of forum topics are replies to service messages about topic creation
+        let topic_roots: HashSet<i64> = exports
+            .iter()
+            .flat_map(|export| &export.messages)
+            .filter(|msg| msg.action.as_deref() == Some("topic_created"))
+            .map(|msg| msg.id)
+            .collect();
+
+        let messages = overrides.apply(Self::messages(exports))?;
 
         // I do this for consistency as messages in different topics can interfere with each other
-        let grouped_by_topic = Self::group_by_topic(messages);
+        let grouped_by_topic = Self::group_by_topic(messages, &topic_roots);
+
+        Ok(Self::person_records(grouped_by_topic, records, persons))
+    }
 
-        Self::person_records(grouped_by_topic)
+    /// Keeps records matching the predicate and people having any of them
+    pub fn retain_records(person_records: &mut PersonRecords, predicate: impl Fn(&Record) -> bool) {
+        for records in person_records.values_mut() {
+            records.retain(&predicate);
+        }
+
+        person_records.retain(|_, records| !records.is_empty());
     }
 
     pub fn messages(exports: Vec<Export>) -> Vec<Message> {
@@ -30,79 +65,270 @@ impl Categorizer {
             .collect_vec()
     }
 
-    pub fn group_by_topic(messages: Vec<Message>) -> HashMap<Option<i64>, Vec<Message>> {
+    pub fn group_by_topic(
+        messages: Vec<Message>,
+        topic_roots: &HashSet<i64>,
+    ) -> HashMap<Option<i64>, Vec<Message>> {
+        let reply_to: HashMap<i64, Option<i64>> = messages
+            .iter()
+            .map(|msg| (msg.id, msg.reply_to_message_id))
+            .collect();
+
         messages
             .into_iter()
-            .map(|msg| (msg.reply_to_message_id, msg))
+            .map(|msg| {
+                let topic_id = Self::topic_id(&reply_to, topic_roots, msg.reply_to_message_id);
+
+                (topic_id, msg)
+            })
             .into_group_map()
     }
 
+    /// Ordinary replies are followed up to the forum topic root, the message that isn't
+    /// a reply (general topic) or a message absent in the export (treated as a topic root)
+    fn topic_id(
+        reply_to: &HashMap<i64, Option<i64>>,
+        topic_roots: &HashSet<i64>,
+        mut parent: Option<i64>,
+    ) -> Option<i64> {
+        // Protection from reply loops in broken exports
+        for _ in 0..=reply_to.len() {
+            let id = parent?;
+
+            if topic_roots.contains(&id) {
+                return Some(id);
+            }
+
+            match reply_to.get(&id) {
+                Some(next) => parent = *next,
+                None => return Some(id),
+            }
+        }
+
+        parent
+    }
+
     pub fn person_records(
         grouped_by_topic: HashMap<Option<i64>, Vec<Message>>,
-    ) -> HashMap<String, Vec<Record>> {
-        grouped_by_topic
+        records: Vec<Record>,
+        persons: &Persons,
+    ) -> (PersonRecords, Vec<SkippedMessage>) {
+        let mut skipped = vec![];
+
+        let mut records = grouped_by_topic
             .into_values()
-            .flat_map(Self::group_messages)
+            .flat_map(|msgs| Self::group_messages(msgs, &mut skipped))
+            .chain(records)
+            .collect_vec();
+
+        persons.canonicalize(&mut records);
+
+        let person_records = records
+            .into_iter()
             .sorted_by_key(|rec| rec.date.clone())
             .rev()
-            .map(|rec| (rec.person.clone(), rec))
-            .into_group_map()
+            // Shared record is in the document of each of its persons
+            .flat_map(|rec| {
+                rec.person
+                    .clone()
+                    .into_iter()
+                    .map(move |person| (person, rec.clone()))
+            })
+            .into_group_map();
+
+        skipped.sort_by_key(|skipped| skipped.message.id);
+
+        (person_records, skipped)
     }
 
-    fn group_messages(mut msgs: Vec<Message>) -> Vec<Record> {
+    fn group_messages(mut msgs: Vec<Message>, skipped: &mut Vec<SkippedMessage>) -> Vec<Record> {
         msgs.sort_by_key(|msg| msg.id);
 
-        // Group is a collection of related messages
-        let mut group: Vec<Message> = vec![];
-        let mut records = vec![];
-        let mut continue_group = false;
+        // Group is a collection of related messages. First message of a group has a record
+        let mut groups: Vec<Vec<Message>> = vec![];
+
+        // Message id to its group index to bind replies to any message of a record
+        let mut message_groups = HashMap::new();
+
+        // Replies to records that aren't parts of them: group index to messages
+        let mut addenda: HashMap<usize, Vec<Message>> = HashMap::new();
+
+        // Addenda message id to its group index to keep replies to addenda together
+        let mut addenda_groups = HashMap::new();
+
+        // Group that following attachments without text continue
+        let mut continued_group = None;
+
+        // Group with explicit `parts: N`: index, remaining parts and the sender of the record
+        let mut parts_group = None;
+
+        // Messages attached by overrides are added once all records of the topic are known
+        let mut attached = vec![];
 
         for msg in msgs {
-            // Will this messages be pushed to group
-            let to_push;
+            // Excluded message outside of a record doesn't break the continuation of others
+            if msg.excluded && !msg.has_record() {
+                skipped.push(SkippedMessage {
+                    reason: SkipReason::Excluded,
+                    message: msg,
+                });
+
+                continue;
+            }
+
+            if msg.part_of.is_some() {
+                attached.push(msg);
 
-            // If true will create record with grouped messages
-            // `group` variable will be emptied
-            let close_prev;
+                continue;
+            }
+
+            if msg.has_broken_record() {
+                let err = msg.get_record().expect_err("Checked above");
+
+                crate::write_err(format!("{} message has invalid record: {err}", msg.id)).ok();
+            }
 
             // Record in msg is an `yaml` block with metadata
-            if msg.has_record() {
-                to_push = true;
-                close_prev = true;
-
-                // Image with record could have following image
-                continue_group = msg.is_photo();
-            } else if msg.is_photo() && msg.is_text_empty() && continue_group {
-                // Image without record can be a part of group
-                // True if group continues
-                to_push = true;
-                close_prev = false;
-            } else {
-                // Only images can create group.
-                // Text and PDFs without Record won't be added to document
-                to_push = false;
-                close_prev = true;
+            if let Ok(record) = msg.get_record() {
+                let index = groups.len();
+
+                message_groups.insert(msg.id, index);
+
+                parts_group = record
+                    .parts
+                    .filter(|parts| *parts > 0)
+                    .map(|parts| (index, parts, msg.sender()));
+
+                // Explicit parts disable implicit continuation
+                continued_group = parts_group.is_none().then_some(index);
+
+                groups.push(vec![msg]);
 
-                continue_group = false;
+                continue;
             }
 
-            if close_prev && !group.is_empty() {
-                records.push(Self::group_to_record(group));
-                group = vec![];
+            // Stickers and other files would make empty pages of a record
+            if msg.is_attachment() && !msg.is_record_part() {
+                skipped.push(SkippedMessage {
+                    reason: SkipReason::UnsupportedAttachment,
+                    message: msg,
+                });
+
+                continue;
             }
 
-            if to_push {
-                group.push(msg);
+            let is_bare_attachment = msg.is_attachment() && msg.is_text_empty();
+
+            // Attachment replying to a record is a part of it wherever it's placed
+            if let Some(&index) = msg
+                .reply_to_message_id
+                .and_then(|id| message_groups.get(&id))
+                .filter(|_| is_bare_attachment)
+            {
+                message_groups.insert(msg.id, index);
+                groups[index].push(msg);
+
+                continue;
+            }
+
+            // Messages of the record sender are its parts until the number is reached.
+            // Messages of others in between are skipped
+            if let Some((index, remaining, sender)) = parts_group.as_mut() {
+                if msg.sender() == *sender {
+                    message_groups.insert(msg.id, *index);
+                    groups[*index].push(msg);
+
+                    *remaining -= 1;
+
+                    if *remaining == 0 {
+                        parts_group = None;
+                    }
+
+                    continue;
+                }
+            }
+
+            // Reply with text to a record or to its addenda is a comment on the record
+            if let Some(&index) = msg
+                .reply_to_message_id
+                .and_then(|id| addenda_groups.get(&id).or(message_groups.get(&id)))
+            {
+                addenda_groups.insert(msg.id, index);
+                addenda.entry(index).or_default().push(msg);
+
+                continue;
+            }
+
+            if let Some(index) = continued_group.filter(|_| is_bare_attachment) {
+                // Attachment without text following a record is a part of the group
+                message_groups.insert(msg.id, index);
+                groups[index].push(msg);
+            } else {
+                // Text and attachments without record and not following one
+                // won't be added to document
+                continued_group = None;
+
+                skipped.push(SkippedMessage {
+                    reason: Self::skip_reason(&msg),
+                    message: msg,
+                });
+            }
+        }
+
+        for msg in attached {
+            match msg.part_of.and_then(|id| message_groups.get(&id)) {
+                Some(&index) => {
+                    groups[index].push(msg);
+
+                    // The record message stays first whatever ids attached messages have
+                    groups[index][1..].sort_by_key(|msg| msg.id);
+                },
+                None => skipped.push(SkippedMessage {
+                    reason: Self::skip_reason(&msg),
+                    message: msg,
+                }),
             }
         }
 
-        if !group.is_empty() {
-            records.push(Self::group_to_record(group));
+        let mut records = vec![];
+
+        for (index, group) in groups.into_iter().enumerate() {
+            let group_addenda = addenda.remove(&index).unwrap_or_default();
+
+            if group[0].excluded {
+                skipped.extend(group.into_iter().chain(group_addenda).map(|message| {
+                    SkippedMessage {
+                        reason: SkipReason::Excluded,
+                        message,
+                    }
+                }));
+
+                continue;
+            }
+
+            let mut record = Self::group_to_record(group);
+            record.addenda = group_addenda;
+
+            records.push(record);
         }
 
         records
     }
 
+    fn skip_reason(msg: &Message) -> SkipReason {
+        if msg.has_broken_record() {
+            let err = msg.get_record().expect_err("Checked by has_broken_record");
+
+            return SkipReason::BrokenRecord(err.to_string());
+        }
+
+        match (msg.is_attachment(), msg.is_text_empty()) {
+            (true, true) => SkipReason::DetachedAttachment,
+            (true, false) => SkipReason::AttachmentWithoutRecord,
+            (false, _) => SkipReason::TextWithoutRecord,
+        }
+    }
+
     fn group_to_record(group: Vec<Message>) -> Record {
         let mut record = group
             .first()
@@ -0,0 +1,222 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use eyre::eyre;
+use serde::Deserialize;
+
+use crate::CheckArgs;
+use crate::archive::Archive;
+use crate::categorizer::Categorizer;
+use crate::overrides::Overrides;
+use crate::persons::Persons;
+use crate::record_date::RecordDate;
+use crate::report::SkipReason;
+use crate::structs::{Message, Record, TextEntity};
+
+const REQUIRED_FIELDS: [&str; 3] = ["date", "person", "tags"];
+
+/// Problem of a source with a link to the message it's found in
+struct Problem {
+    link: String,
+    description: String,
+}
+
+/// Lints sources for records that would silently disappear from documents
+pub struct Check;
+
+impl Check {
+    pub fn run(args: &CheckArgs) -> eyre::Result<()> {
+        let overrides = args
+            .overrides
+            .as_deref()
+            .map(Overrides::read)
+            .transpose()?
+            .unwrap_or_default();
+
+        let persons = args
+            .persons
+            .as_deref()
+            .map(Persons::read)
+            .transpose()?
+            .unwrap_or_default();
+
+        let problems = Self::problems(&args.sources, &overrides, &persons)?;
+
+        for problem in &problems {
+            println!("{}: {}", problem.link, problem.description);
+        }
+
+        if !problems.is_empty() {
+            return Err(eyre!("{} problems found", problems.len()));
+        }
+
+        println!("no problems found");
+
+        Ok(())
+    }
+
+    fn problems(
+        sources: &[String],
+        overrides: &Overrides,
+        persons: &Persons,
+    ) -> eyre::Result<Vec<Problem>> {
+        let mut problems = vec![];
+        let mut exports = vec![];
+
+        for source in sources {
+            match crate::get_export_result(source) {
+                Ok(export) => exports.push(export),
+                Err(err) => problems.push(Problem {
+                    link: source.clone(),
+                    description: err.to_string(),
+                }),
+            }
+        }
+
+        let chat_id = exports
+            .iter()
+            .map(|export| export.id)
+            .find(|id| *id != 0)
+            .unwrap_or_default();
+
+        let messages = overrides.apply(Categorizer::messages(exports.clone()))?;
+
+        for msg in messages.iter().filter(|msg| !msg.excluded) {
+            problems.extend(
+                Self::record_block_problems(msg)
+                    .into_iter()
+                    .map(|description| Problem {
+                        link: msg.link(chat_id),
+                        description,
+                    }),
+            );
+        }
+
+        let (person_records, skipped) = Categorizer::process_exports(exports, overrides, persons)?;
+
+        // Messages dropped by overrides on purpose aren't problems
+        let excluded: HashSet<i64> = skipped
+            .iter()
+            .filter(|skipped| skipped.reason == SkipReason::Excluded)
+            .map(|skipped| skipped.message.id)
+            .collect();
+
+        // Archives are listed once per export instead of once per file
+        let mut archive_files = HashMap::new();
+        let mut recorded = HashSet::new();
+
+        for msg in person_records
+            .values()
+            .flatten()
+            .flat_map(|rec| rec.messages.iter().chain(&rec.addenda))
+        {
+            recorded.insert(msg.id);
+
+            for file in [&msg.photo, &msg.file].into_iter().flatten() {
+                if !Self::file_exists(&mut archive_files, msg, file)? {
+                    problems.push(Problem {
+                        link: msg.link(chat_id),
+                        description: format!("file {file} is missing"),
+                    });
+                }
+            }
+        }
+
+        for msg in &messages {
+            if !recorded.contains(&msg.id)
+                && !excluded.contains(&msg.id)
+                && (msg.is_photo() || msg.is_pdf())
+            {
+                problems.push(Problem {
+                    link: msg.link(chat_id),
+                    description: "photo or pdf isn't a part of any record".into(),
+                });
+            }
+        }
+
+        Ok(problems)
+    }
+
+    /// Problems of the leading code block if it's meant to be a record
+    fn record_block_problems(msg: &Message) -> Vec<String> {
+        let Some(TextEntity::Pre { text, language }) = msg.text_entities.first() else {
+            return vec![];
+        };
+
+        if matches!(language.as_str(), "html" | "csv") {
+            return vec![];
+        }
+
+        // Location of serde_yaml errors is a part of their messages
+        let mapping = match serde_yaml::from_str(&RecordDate::quote_yaml(text)) {
+            Ok(serde_yaml::Value::Mapping(mapping)) => mapping,
+            Ok(_) => return vec![],
+            Err(err) => return vec![format!("invalid yaml: {err}")],
+        };
+
+        let keys = mapping
+            .keys()
+            .map(|key| {
+                key.as_str()
+                    .map(str::to_string)
+                    .unwrap_or(format!("{key:?}"))
+            })
+            .collect::<Vec<_>>();
+
+        // Code block without any record field is just a code block
+        if !keys
+            .iter()
+            .any(|key| Record::FIELDS.contains(&key.as_str()))
+        {
+            return vec![];
+        }
+
+        let mut problems = vec![];
+
+        for field in REQUIRED_FIELDS {
+            if !mapping.contains_key(field) {
+                problems.push(format!("missing required field `{field}`"));
+            }
+        }
+
+        for key in keys
+            .iter()
+            .filter(|key| !Record::FIELDS.contains(&key.as_str()))
+        {
+            problems.push(format!("unknown field `{key}`"));
+        }
+
+        if let Some(date) = mapping.get("date") {
+            if let Err(err) = RecordDate::deserialize(date.clone()) {
+                problems.push(format!("invalid date: {err}"));
+            }
+        }
+
+        if problems.is_empty() {
+            if let Err(err) = msg.get_record() {
+                problems.push(format!("invalid record: {err}"));
+            }
+        }
+
+        problems
+    }
+
+    fn file_exists(
+        archive_files: &mut HashMap<PathBuf, Option<HashSet<String>>>,
+        msg: &Message,
+        file: &str,
+    ) -> eyre::Result<bool> {
+        let export_path = msg.unwrap_export_path();
+
+        if !archive_files.contains_key(&export_path) {
+            archive_files.insert(export_path.clone(), Archive::files(&export_path)?);
+        }
+
+        let exists = match &archive_files[&export_path] {
+            Some(files) => files.contains(file.trim_start_matches("./")),
+            None => export_path.join(file).is_file(),
+        };
+
+        Ok(exists)
+    }
+}
@@ -70,6 +70,30 @@ pub fn img2pdf(
     cmd("img2pdf", args)
 }
 
+pub fn convert(
+    args: impl IntoIterator<Item = impl AsRef<OsStr>> + std::fmt::Debug,
+) -> eyre::Result<CommandResult> {
+    cmd("convert", args)
+}
+
+pub fn soffice(
+    args: impl IntoIterator<Item = impl AsRef<OsStr>> + std::fmt::Debug,
+) -> eyre::Result<CommandResult> {
+    cmd("soffice", args)
+}
+
+pub fn ffmpeg(
+    args: impl IntoIterator<Item = impl AsRef<OsStr>> + std::fmt::Debug,
+) -> eyre::Result<CommandResult> {
+    cmd("ffmpeg", args)
+}
+
+pub fn whisper(
+    args: impl IntoIterator<Item = impl AsRef<OsStr>> + std::fmt::Debug,
+) -> eyre::Result<CommandResult> {
+    cmd("whisper-cli", args)
+}
+
 pub fn pdfunite(
     pdfs: impl IntoIterator<Item = impl AsRef<OsStr>> + std::fmt::Debug,
 ) -> eyre::Result<CommandResult> {
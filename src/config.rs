@@ -0,0 +1,215 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches};
+use eyre::eyre;
+
+use crate::Args;
+
+const CONFIG_FILE: &str = "medpack.toml";
+
+/// Options with paths resolved against the directory of the config
+const PATH_OPTIONS: [&str; 6] = [
+    "output_dir",
+    "overrides",
+    "persons",
+    "skipped_report",
+    "sources",
+    "whisper_model",
+];
+
+/// `medpack.toml` with defaults of command line options and named profiles:
+///
+/// ```toml
+/// no_ocr = true
+/// sources = ["./ChatExport_2023", "./ChatExport_2024"]
+///
+/// [profiles.cardiologist]
+/// people = ["maria"]
+/// ```
+///
+/// Options given in the command line take precedence over the profile,
+/// and the profile takes precedence over the top level of the file.
+/// Relative paths are resolved against the directory of the file
+pub struct Config;
+
+impl Config {
+    pub fn parse_args() -> eyre::Result<Args> {
+        let mut argv: Vec<OsString> = env::args_os().collect();
+
+        let command = Args::command();
+        let matches = command.clone().get_matches_from(&argv);
+
+        let (subcommand, sub_matches) = match matches.subcommand() {
+            Some((name, sub_matches)) => (
+                command.find_subcommand(name).expect("Parsed subcommand"),
+                sub_matches,
+            ),
+            None => (&command, &matches),
+        };
+
+        let profile = Self::get_one::<String>(sub_matches, "profile");
+
+        let Some(path) = Self::find(sub_matches) else {
+            if let Some(profile) = profile {
+                return Err(eyre!(
+                    "profile {profile} is set but {CONFIG_FILE} isn't found"
+                ));
+            }
+
+            return Ok(Args::from_arg_matches(&matches)?);
+        };
+
+        let mut options = fs::read_to_string(&path)?
+            .parse::<toml::Table>()
+            .map_err(|err| eyre!("{}: {err}", path.display()))?;
+
+        let profiles = options.remove("profiles");
+
+        // Every option of the file must be a build option even if it's unused by the command
+        let build = command.find_subcommand("build").expect("Build subcommand");
+
+        let tables = profiles
+            .iter()
+            .flat_map(|profiles| profiles.as_table())
+            .flat_map(|profiles| profiles.values())
+            .map(|profile| {
+                profile
+                    .as_table()
+                    .ok_or_else(|| eyre!("{}: profiles must be tables", path.display()))
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        for key in tables
+            .into_iter()
+            .chain([&options])
+            .flat_map(|table| table.keys())
+        {
+            let id = key.replace('-', "_");
+
+            let is_option = build.get_arguments().any(|arg| {
+                arg.get_id() == id.as_str() && !["config", "profile"].contains(&id.as_str())
+            });
+
+            if !is_option {
+                return Err(eyre!("{}: unknown option {key}", path.display()));
+            }
+        }
+
+        if let Some(profile) = profile {
+            let profile = profiles
+                .as_ref()
+                .and_then(|profiles| profiles.get(&profile))
+                .and_then(|profile| profile.as_table())
+                .ok_or_else(|| eyre!("{}: profile {profile} isn't found", path.display()))?;
+
+            options.extend(profile.clone());
+        }
+
+        let mut options_args = vec![];
+        let mut positional_args = vec![];
+
+        for (key, value) in options {
+            let id = key.replace('-', "_");
+
+            // Options of other commands
+            let Some(arg) = subcommand
+                .get_arguments()
+                .find(|arg| arg.get_id() == id.as_str())
+            else {
+                continue;
+            };
+
+            if sub_matches.value_source(&id) == Some(ValueSource::CommandLine) {
+                continue;
+            }
+
+            let mut values = Self::values(&value)
+                .ok_or_else(|| eyre!("{}: invalid value of {key}", path.display()))?;
+
+            if PATH_OPTIONS.contains(&id.as_str()) {
+                let dir = path.parent().unwrap_or(Path::new(""));
+
+                values = values
+                    .into_iter()
+                    .map(|value| dir.join(value).to_string_lossy().to_string())
+                    .collect();
+            }
+
+            if arg.is_positional() {
+                positional_args.extend(values);
+            } else if matches!(arg.get_action(), ArgAction::SetTrue) {
+                if value
+                    .as_bool()
+                    .ok_or_else(|| eyre!("{}: {key} must be a boolean", path.display()))?
+                {
+                    options_args.push(Self::flag(arg));
+                }
+            } else {
+                for value in values {
+                    options_args.extend([Self::flag(arg), value]);
+                }
+            }
+        }
+
+        argv.extend(options_args.into_iter().map(OsString::from));
+        argv.extend(positional_args.into_iter().map(OsString::from));
+
+        Ok(Args::from_arg_matches(
+            &Args::command().get_matches_from(argv),
+        )?)
+    }
+
+    /// Config of `--config`, the current directory or the first source directory having one
+    fn find(matches: &ArgMatches) -> Option<PathBuf> {
+        if let Some(path) = Self::get_one::<PathBuf>(matches, "config") {
+            return Some(path);
+        }
+
+        let sources = matches
+            .try_get_many::<String>("sources")
+            .ok()
+            .flatten()
+            .into_iter()
+            .flatten()
+            .map(PathBuf::from);
+
+        [PathBuf::from(".")]
+            .into_iter()
+            .chain(sources)
+            .map(|dir| dir.join(CONFIG_FILE))
+            .find(|path| Path::is_file(path))
+    }
+
+    fn get_one<T: Clone + Send + Sync + 'static>(matches: &ArgMatches, id: &str) -> Option<T> {
+        matches.try_get_one::<T>(id).ok().flatten().cloned()
+    }
+
+    fn flag(arg: &clap::Arg) -> String {
+        match (arg.get_long(), arg.get_short()) {
+            (Some(long), _) => format!("--{long}"),
+            (None, Some(short)) => format!("-{short}"),
+            (None, None) => unreachable!("Options have a long or a short name"),
+        }
+    }
+
+    fn values(value: &toml::Value) -> Option<Vec<String>> {
+        let values = match value {
+            toml::Value::String(value) => vec![value.clone()],
+            toml::Value::Integer(value) => vec![value.to_string()],
+            toml::Value::Float(value) => vec![value.to_string()],
+            toml::Value::Boolean(value) => vec![value.to_string()],
+            toml::Value::Datetime(value) => vec![value.to_string()],
+            toml::Value::Array(values) => values
+                .iter()
+                .map(Self::values)
+                .collect::<Option<Vec<_>>>()?
+                .concat(),
+            toml::Value::Table(_) => return None,
+        };
+
+        Some(values)
+    }
+}
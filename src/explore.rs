@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::ExploreArgs;
+use crate::categorizer::{Categorizer, PersonRecords};
+use crate::overrides::Overrides;
+use crate::persons::Persons;
+use crate::record_date::Period;
+use crate::record_field::RecordField;
+use crate::structs::Record;
+
+#[derive(Serialize)]
+struct ListItem {
+    person: String,
+    date: String,
+    tags: Vec<String>,
+    doctor: Option<String>,
+    place: Option<String>,
+    shared_with: Vec<String>,
+    link: String,
+}
+
+#[derive(Serialize)]
+struct PersonStats {
+    person: String,
+    records: usize,
+    first_date: Option<String>,
+    last_date: Option<String>,
+    tags: BTreeMap<String, usize>,
+    doctors: BTreeMap<String, usize>,
+    places: BTreeMap<String, usize>,
+}
+
+/// Overview of records without building documents
+pub struct Explore;
+
+impl Explore {
+    pub fn list(args: &ExploreArgs) -> eyre::Result<()> {
+        let (chat_id, person_records) = Self::person_records(args)?;
+
+        let items = person_records
+            .iter()
+            .sorted_by_key(|(person, _)| *person)
+            .flat_map(|(person, records)| records.iter().map(move |rec| (person, rec)))
+            .map(|(person, rec)| ListItem {
+                person: person.clone(),
+                date: rec.date.to_string(),
+                tags: rec
+                    .tags
+                    .iter()
+                    .map(|tag| crate::strip_html_tags(tag))
+                    .collect(),
+                doctor: rec.doctor.clone(),
+                place: rec.place.clone(),
+                shared_with: rec
+                    .person
+                    .iter()
+                    .filter(|other| *other != person)
+                    .cloned()
+                    .collect(),
+                link: rec.link(chat_id),
+            })
+            .collect_vec();
+
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&items)?);
+
+            return Ok(());
+        }
+
+        let rows = items
+            .into_iter()
+            .map(|item| {
+                vec![
+                    item.person,
+                    item.date,
+                    item.tags.join(", "),
+                    item.doctor.unwrap_or_default(),
+                    item.place.unwrap_or_default(),
+                    item.link,
+                ]
+            })
+            .collect_vec();
+
+        Self::print_table(&["person", "date", "tags", "doctor", "place", "link"], rows);
+
+        Ok(())
+    }
+
+    pub fn stats(args: &ExploreArgs) -> eyre::Result<()> {
+        let (_, person_records) = Self::person_records(args)?;
+
+        let stats = person_records
+            .iter()
+            .sorted_by_key(|(person, _)| *person)
+            .map(|(person, records)| PersonStats {
+                person: person.clone(),
+                records: records.len(),
+                first_date: records
+                    .iter()
+                    .map(|rec| &rec.date)
+                    .min()
+                    .map(ToString::to_string),
+                last_date: records
+                    .iter()
+                    .map(|rec| &rec.date)
+                    .max()
+                    .map(ToString::to_string),
+                tags: Self::frequencies(records, |rec| {
+                    rec.tags
+                        .iter()
+                        .map(|tag| crate::strip_html_tags(tag))
+                        .collect()
+                }),
+                doctors: Self::frequencies(records, |rec| rec.doctor.iter().cloned().collect()),
+                places: Self::frequencies(records, |rec| rec.place.iter().cloned().collect()),
+            })
+            .collect_vec();
+
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+
+            return Ok(());
+        }
+
+        let rows = stats
+            .iter()
+            .map(|stats| {
+                vec![
+                    stats.person.clone(),
+                    stats.records.to_string(),
+                    stats.first_date.clone().unwrap_or_default(),
+                    stats.last_date.clone().unwrap_or_default(),
+                ]
+            })
+            .collect_vec();
+
+        Self::print_table(&["person", "records", "from", "to"], rows);
+
+        for stats in &stats {
+            for (name, frequencies) in [
+                ("tags", &stats.tags),
+                ("doctors", &stats.doctors),
+                ("places", &stats.places),
+            ] {
+                if frequencies.is_empty() {
+                    continue;
+                }
+
+                println!("\n{} {name}", stats.person);
+
+                let rows = frequencies
+                    .iter()
+                    // The most frequent first, alphabetically among equal ones
+                    .sorted_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)))
+                    .map(|(value, count)| vec![count.to_string(), value.clone()])
+                    .collect_vec();
+
+                Self::print_table(&["count", name], rows);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn person_records(args: &ExploreArgs) -> eyre::Result<(i64, PersonRecords)> {
+        let exports = args
+            .sources
+            .iter()
+            .map(|path| crate::get_export_result(path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let chat_id = exports
+            .iter()
+            .map(|export| export.id)
+            .find(|id| *id != 0)
+            .unwrap_or_default();
+
+        let overrides = args
+            .overrides
+            .as_deref()
+            .map(Overrides::read)
+            .transpose()?
+            .unwrap_or_default();
+
+        let persons = args
+            .persons
+            .as_deref()
+            .map(Persons::read)
+            .transpose()?
+            .unwrap_or_default();
+
+        let (mut person_records, _) = Categorizer::process_exports(exports, &overrides, &persons)?;
+
+        persons.retain_people(&args.people, &mut person_records);
+
+        Period::new(args.since.as_deref(), args.until.as_deref())?
+            .retain_records(&mut person_records);
+
+        if let Some(tags) = &args.tags {
+            tags.retain_records(&mut person_records);
+        }
+
+        RecordField::Doctor.retain_records(&args.doctor, args.exact_match, &mut person_records);
+        RecordField::Place.retain_records(&args.place, args.exact_match, &mut person_records);
+
+        Ok((chat_id, person_records))
+    }
+
+    fn frequencies(
+        records: &[Record],
+        values: impl Fn(&Record) -> Vec<String>,
+    ) -> BTreeMap<String, usize> {
+        let mut frequencies = BTreeMap::new();
+
+        for value in records.iter().flat_map(values) {
+            *frequencies.entry(value).or_default() += 1;
+        }
+
+        frequencies
+    }
+
+    fn print_table(header: &[&str], rows: Vec<Vec<String>>) {
+        let widths = header
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                rows.iter()
+                    .map(|row| row[index].chars().count())
+                    .chain([name.chars().count()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect_vec();
+
+        let header = header.iter().map(ToString::to_string).collect_vec();
+
+        for row in [header].into_iter().chain(rows) {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(value, width)| format!("{value:<width$}"))
+                .join("  ");
+
+            println!("{}", line.trim_end());
+        }
+    }
+}
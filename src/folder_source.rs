@@ -0,0 +1,165 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use eyre::eyre;
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::record_date::RecordDate;
+use crate::structs::{self, Export, Message, Record};
+
+const INDEX_FILE: &str = "index.yaml";
+
+// Folder documents have no message ids, so negative ones are used
+// to never clash with Telegram messages
+static NEXT_ID: AtomicI64 = AtomicI64::new(-1);
+
+#[derive(Deserialize)]
+struct IndexEntry {
+    #[serde(flatten)]
+    record: Record,
+    files: Vec<PathBuf>,
+}
+
+/// Plain folder with documents where every document has a `*.yaml` sidecar
+/// with record metadata or is listed in a directory-level `index.yaml`
+pub struct FolderSource;
+
+impl FolderSource {
+    pub fn is_source(source_path: &Path) -> eyre::Result<bool> {
+        if source_path.join(INDEX_FILE).exists() {
+            return Ok(true);
+        }
+
+        let has_sidecar = fs::read_dir(source_path)?
+            .map_ok(|entry| entry.path())
+            .filter_ok(|path| Self::is_yaml(path))
+            .collect::<Result<Vec<_>, _>>()?
+            .iter()
+            .any(|path| Self::sidecar_document(path).is_some());
+
+        Ok(has_sidecar)
+    }
+
+    pub fn read(source_path: &Path) -> eyre::Result<Export> {
+        let mut export = Export {
+            name: source_path.to_string_lossy().to_string(),
+            type_field: "folder".into(),
+            ..Default::default()
+        };
+
+        Self::read_dir(source_path, source_path, &mut export.records)?;
+
+        Ok(export)
+    }
+
+    fn read_dir(source_path: &Path, dir: &Path, records: &mut Vec<Record>) -> eyre::Result<()> {
+        let paths = fs::read_dir(dir)?
+            .map_ok(|entry| entry.path())
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .sorted()
+            .collect_vec();
+
+        let index_path = dir.join(INDEX_FILE);
+
+        if index_path.exists() {
+            let entries: Vec<IndexEntry> =
+                serde_yaml::from_str(&RecordDate::quote_yaml(&fs::read_to_string(&index_path)?))
+                    .map_err(|err| eyre!("{}: {err}", index_path.display()))?;
+
+            for entry in entries {
+                let files = entry.files.iter().map(|file| dir.join(file)).collect_vec();
+
+                records.push(Self::to_record(source_path, entry.record, &files)?);
+            }
+        }
+
+        for path in paths {
+            if path.is_dir() {
+                Self::read_dir(source_path, &path, records)?;
+
+                continue;
+            }
+
+            if !Self::is_yaml(&path) || path == index_path {
+                continue;
+            }
+
+            // Other YAML files like persons.yaml or overrides.yaml may be kept
+            // in the folder too
+            let Some(document) = Self::sidecar_document(&path) else {
+                continue;
+            };
+
+            let record: Record =
+                serde_yaml::from_str(&RecordDate::quote_yaml(&fs::read_to_string(&path)?))
+                    .map_err(|err| eyre!("{}: {err}", path.display()))?;
+
+            records.push(Self::to_record(source_path, record, &[document])?);
+        }
+
+        Ok(())
+    }
+
+    fn to_record(
+        source_path: &Path,
+        mut record: Record,
+        files: &[PathBuf],
+    ) -> eyre::Result<Record> {
+        record.messages = files
+            .iter()
+            .map(|file| Self::to_message(source_path, file))
+            .collect::<Result<_, _>>()?;
+
+        Ok(record)
+    }
+
+    fn to_message(source_path: &Path, file: &Path) -> eyre::Result<Message> {
+        if !file.exists() {
+            return Err(eyre!("{} doesn't exist", file.display()));
+        }
+
+        let relative = file.strip_prefix(source_path).unwrap_or(file);
+        let date = fs::metadata(file)?.modified()?;
+        let date = chrono::DateTime::<chrono::Utc>::from(date);
+
+        Ok(Message {
+            id: NEXT_ID.fetch_sub(1, Ordering::Relaxed),
+            type_field: "message".into(),
+            date: date.naive_utc(),
+            date_unixtime: date.timestamp().to_string(),
+            file: Some(relative.to_string_lossy().to_string()),
+            mime_type: structs::guess_mime_type(file),
+            export_path: Some(source_path.to_path_buf()),
+            link: Some(format!("file://{}", fs::canonicalize(file)?.display())),
+            ..Default::default()
+        })
+    }
+
+    fn is_yaml(path: &Path) -> bool {
+        path.is_file()
+            && path.extension().is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml")
+            })
+    }
+
+    /// Any file of a known type
+    fn is_document(path: &Path) -> bool {
+        structs::guess_mime_type(path)
+            .is_some_and(|mime_type| mime_type != "application/octet-stream")
+    }
+
+    /// Document with the same file stem as the sidecar
+    fn sidecar_document(sidecar: &Path) -> Option<PathBuf> {
+        let stem = sidecar.file_stem()?;
+        let dir = sidecar.parent()?;
+
+        fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.file_stem() == Some(stem) && path.is_file())
+            .find(|path| Self::is_document(path))
+    }
+}
@@ -0,0 +1,369 @@
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use chrono::{DateTime, NaiveDateTime};
+use ego_tree::NodeRef;
+use eyre::eyre;
+use itertools::Itertools;
+use regex::Regex;
+use scraper::{CaseSensitivity, ElementRef, Html, Node, Selector};
+
+use crate::structs::{self, Export, Message, TextEntity};
+
+static MESSAGE: LazyLock<Selector> = LazyLock::new(|| Selector::parse("div.message").unwrap());
+static BODY: LazyLock<Selector> = LazyLock::new(|| Selector::parse(":scope > div.body").unwrap());
+static DATE: LazyLock<Selector> = LazyLock::new(|| Selector::parse("div.date").unwrap());
+static FROM_NAME: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse(":scope > div.from_name").unwrap());
+static FORWARDED: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("div.forwarded div.from_name").unwrap());
+static REPLY_TO: LazyLock<Selector> = LazyLock::new(|| Selector::parse("div.reply_to a").unwrap());
+static TEXT: LazyLock<Selector> = LazyLock::new(|| Selector::parse("div.text").unwrap());
+static PHOTO: LazyLock<Selector> = LazyLock::new(|| Selector::parse("a.photo_wrap").unwrap());
+static MEDIA: LazyLock<Selector> = LazyLock::new(|| {
+    Selector::parse(
+        "a.media_file, a.media_voice_message, a.media_audio_file, a.video_file_wrap, \
+         a.animated_wrap, a.video_message_wrap, a.sticker_wrap",
+    )
+    .unwrap()
+});
+static THUMBNAIL: LazyLock<Selector> = LazyLock::new(|| Selector::parse("img").unwrap());
+static DURATION: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("div.video_duration, div.status").unwrap());
+static CHAT_NAME: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("div.page_header div.text").unwrap());
+
+static MESSAGE_ID: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^message(\d+)$").unwrap());
+static GO_TO_MESSAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:go_to_message|GoToMessage\()(\d+)").unwrap());
+static PAGE_FILE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^messages(\d*)\.html$").unwrap());
+static DURATION_VALUE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:(\d+):)?(\d+):(\d+)").unwrap());
+
+/// Reader of Telegram Desktop exports made in the default HTML format
+/// (`messages.html`, `messages2.html`, ...)
+pub struct HtmlExport;
+
+impl HtmlExport {
+    pub fn is_export(export_path: &Path) -> bool {
+        export_path.join("messages.html").exists()
+    }
+
+    pub fn is_page(file_name: &str) -> bool {
+        PAGE_FILE.is_match(file_name)
+    }
+
+    pub fn read(export_path: &Path) -> eyre::Result<Export> {
+        let mut pages = vec![];
+
+        for entry in fs::read_dir(export_path)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+
+            if Self::is_page(&name) {
+                let content = fs::read_to_string(export_path.join(&name))?;
+
+                pages.push((name, content));
+            }
+        }
+
+        Self::from_pages(pages)
+    }
+
+    /// Builds export from `(file name, content)` pairs of message pages in any order
+    pub fn from_pages(pages: impl IntoIterator<Item = (String, String)>) -> eyre::Result<Export> {
+        let pages = pages
+            .into_iter()
+            .sorted_by_key(|(name, _)| {
+                PAGE_FILE
+                    .captures(name)
+                    .and_then(|cap| cap[1].parse::<u32>().ok())
+                    .unwrap_or(1)
+            })
+            .collect_vec();
+
+        let mut export = Export {
+            type_field: "html_export".into(),
+            ..Default::default()
+        };
+
+        // Joined messages don't repeat the sender, so it's carried between pages
+        let mut from = None;
+
+        for (page, content) in pages {
+            let html = Html::parse_document(&content);
+
+            if export.name.is_empty() {
+                if let Some(name) = html.select(&CHAT_NAME).next() {
+                    export.name = name.text().collect::<String>().trim().to_string();
+                }
+            }
+
+            for element in html.select(&MESSAGE) {
+                let message = Self::parse_message(element, &mut from)
+                    .map_err(|err| eyre!("{page}: {err}"))?;
+
+                if let Some(message) = message {
+                    export.messages.push(message);
+                }
+            }
+        }
+
+        Ok(export)
+    }
+
+    fn parse_message(
+        element: ElementRef,
+        from: &mut Option<String>,
+    ) -> eyre::Result<Option<Message>> {
+        let Some(id) = element
+            .attr("id")
+            .and_then(|id| MESSAGE_ID.captures(id))
+            .map(|cap| cap[1].parse::<i64>())
+            .transpose()?
+        else {
+            return Ok(None);
+        };
+
+        let classes = element.value().classes().collect_vec();
+
+        if classes.contains(&"service") {
+            let text = element.text().collect::<String>();
+
+            return Ok(Some(Message {
+                id,
+                type_field: "service".into(),
+                action: text
+                    .contains("created topic")
+                    .then(|| "topic_created".to_string()),
+                ..Default::default()
+            }));
+        }
+
+        let Some(body) = element.select(&BODY).next() else {
+            return Ok(None);
+        };
+
+        let date_title = body
+            .select(&DATE)
+            .next()
+            .and_then(|date| date.attr("title"))
+            .ok_or_else(|| eyre!("message {id} has no date"))?;
+
+        let (date, date_unixtime) = Self::parse_date(date_title)?;
+
+        if let Some(name) = body.select(&FROM_NAME).next() {
+            *from = Some(name.text().collect::<String>().trim().to_string());
+        } else if !classes.contains(&"joined") {
+            *from = None;
+        }
+
+        let mut message = Message {
+            id,
+            type_field: "message".into(),
+            date,
+            date_unixtime: date_unixtime.to_string(),
+            from: from.clone(),
+            forwarded_from: body
+                .select(&FORWARDED)
+                .next()
+                .map(|name| name.text().collect::<String>().trim().to_string()),
+            reply_to_message_id: body
+                .select(&REPLY_TO)
+                .next()
+                .and_then(|link| link.attr("href").or(link.attr("onclick")))
+                .and_then(|link| GO_TO_MESSAGE.captures(link))
+                .and_then(|cap| cap[1].parse().ok()),
+            text_entities: body
+                .select(&TEXT)
+                .next()
+                .map(Self::parse_text)
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+
+        if let Some(photo) = body.select(&PHOTO).next() {
+            message.photo = photo.attr("href").map(str::to_string);
+        }
+
+        if let Some(media) = body.select(&MEDIA).next() {
+            let media_classes = media.value().classes().collect_vec();
+
+            message.media_type = [
+                ("media_voice_message", "voice_message"),
+                ("media_audio_file", "audio_file"),
+                ("video_file_wrap", "video_file"),
+                ("animated_wrap", "animation"),
+                ("video_message_wrap", "video_message"),
+                ("sticker_wrap", "sticker"),
+            ]
+            .into_iter()
+            .find(|(class, _)| media_classes.contains(class))
+            .map(|(_, media_type)| media_type.to_string());
+
+            message.file = media.attr("href").map(str::to_string);
+            message.mime_type = message
+                .file
+                .as_deref()
+                .and_then(|file| structs::guess_mime_type(Path::new(file)));
+            message.thumbnail = media
+                .select(&THUMBNAIL)
+                .next()
+                .and_then(|img| img.attr("src"))
+                .map(str::to_string);
+            message.duration_seconds = media
+                .select(&DURATION)
+                .next()
+                .and_then(|duration| Self::parse_duration(&duration.text().collect::<String>()));
+        }
+
+        Ok(Some(message))
+    }
+
+    /// Date title looks like `22.12.2023 10:11:12 UTC+03:00`, where the
+    /// timezone part is absent in older versions of Telegram Desktop
+    fn parse_date(title: &str) -> eyre::Result<(NaiveDateTime, i64)> {
+        let title = title.trim();
+        let local = title.get(..19).unwrap_or(title);
+        let date = NaiveDateTime::parse_from_str(local, "%d.%m.%Y %H:%M:%S")?;
+
+        let unixtime = match title
+            .get(19..)
+            .map(|tz| tz.trim().trim_start_matches("UTC"))
+        {
+            Some(offset) if !offset.is_empty() => {
+                DateTime::parse_from_str(&format!("{local} {offset}"), "%d.%m.%Y %H:%M:%S %:z")?
+                    .timestamp()
+            },
+            _ => date.and_utc().timestamp(),
+        };
+
+        Ok((date, unixtime))
+    }
+
+    fn parse_duration(text: &str) -> Option<i64> {
+        let cap = DURATION_VALUE.captures(text)?;
+
+        let hours: i64 = cap
+            .get(1)
+            .map_or(Ok(0), |hours| hours.as_str().parse())
+            .ok()?;
+        let minutes: i64 = cap[2].parse().ok()?;
+        let seconds: i64 = cap[3].parse().ok()?;
+
+        Some(hours * 3600 + minutes * 60 + seconds)
+    }
+
+    fn parse_text(text: ElementRef) -> Vec<TextEntity> {
+        let mut entities = vec![];
+
+        for node in text.children() {
+            Self::push_entity(&mut entities, node);
+        }
+
+        // Markup indentation around the text isn't a part of the message
+        if let Some(TextEntity::Plain { text }) = entities.first_mut() {
+            *text = text.trim_start().to_string();
+        }
+
+        if let Some(TextEntity::Plain { text }) = entities.last_mut() {
+            *text = text.trim_end().to_string();
+        }
+
+        entities.retain(|entity| !matches!(entity, TextEntity::Plain { text } if text.is_empty()));
+
+        entities
+    }
+
+    fn push_entity(entities: &mut Vec<TextEntity>, node: NodeRef<Node>) {
+        let entity = match node.value() {
+            Node::Text(text) => TextEntity::Plain {
+                text: text.replace('\n', ""),
+            },
+            Node::Element(element) => {
+                let element_ref = ElementRef::wrap(node).expect("Node is an element");
+                let text = Self::element_text(element_ref);
+
+                match element.name() {
+                    "br" => TextEntity::Plain { text: "\n".into() },
+                    "strong" | "b" => TextEntity::Bold { text },
+                    "em" | "i" => TextEntity::Italic { text },
+                    "code" => TextEntity::Code { text },
+                    "s" | "del" => TextEntity::Strikethrough { text },
+                    "u" => TextEntity::Underline { text },
+                    "blockquote" => TextEntity::Blockquote {
+                        text,
+                        collapsed: false,
+                    },
+                    "span" if element.has_class("spoiler", CaseSensitivity::CaseSensitive) => {
+                        TextEntity::Spoiler { text }
+                    },
+                    "pre" => TextEntity::Pre {
+                        text,
+                        language: element
+                            .classes()
+                            .find_map(|class| class.strip_prefix("language-"))
+                            .unwrap_or_default()
+                            .to_string(),
+                    },
+                    "a" => Self::link_entity(element_ref, text),
+                    _ => TextEntity::Plain { text },
+                }
+            },
+            _ => return,
+        };
+
+        // Consecutive plain parts are merged the same way Telegram does in json exports
+        if let (TextEntity::Plain { text }, Some(TextEntity::Plain { text: prev })) =
+            (&entity, entities.last_mut())
+        {
+            prev.push_str(text);
+            return;
+        }
+
+        entities.push(entity);
+    }
+
+    fn link_entity(link: ElementRef, text: String) -> TextEntity {
+        let href = link.attr("href").unwrap_or_default();
+        let onclick = link.attr("onclick").unwrap_or_default();
+
+        if onclick.contains("ShowHashtag") {
+            TextEntity::Hashtag { text }
+        } else if onclick.contains("ShowCashtag") {
+            TextEntity::Cashtag { text }
+        } else if onclick.contains("ShowBotCommand") {
+            TextEntity::BotCommand { text }
+        } else if onclick.contains("ShowMentionName") {
+            TextEntity::MentionName {
+                text,
+                user_id: None,
+            }
+        } else if text.starts_with('@') && href.starts_with("https://t.me/") {
+            TextEntity::Mention { text }
+        } else if href.starts_with("mailto:") {
+            TextEntity::Email { text }
+        } else if href.starts_with("tel:") {
+            TextEntity::Phone { text }
+        } else if href.is_empty() || href == text {
+            TextEntity::Link { text }
+        } else {
+            TextEntity::TextLink {
+                text,
+                href: href.to_string(),
+            }
+        }
+    }
+
+    /// Text of the element where `<br>` is converted back to new lines
+    fn element_text(element: ElementRef) -> String {
+        element
+            .descendants()
+            .filter_map(|node| match node.value() {
+                Node::Text(text) => Some(text.to_string()),
+                Node::Element(element) if element.name() == "br" => Some("\n".into()),
+                _ => None,
+            })
+            .join("")
+    }
+}
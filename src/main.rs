@@ -1,36 +1,175 @@
 #![feature(exit_status_error)]
 use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::fmt::Display;
 use std::fs::{self, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 use backon::{BlockingRetryable, ConstantBuilder};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use eyre::Ok;
 use indicatif::{HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
 use itertools::Itertools;
 use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use regex::Regex;
 use scraper::Html;
 
 use crate::app::App;
+use crate::archive::Archive;
 use crate::categorizer::Categorizer;
+use crate::check::Check;
+use crate::config::Config;
+use crate::explore::Explore;
+use crate::folder_source::FolderSource;
+use crate::html_export::HtmlExport;
+use crate::output::Output;
+use crate::overrides::Overrides;
 use crate::pdf_tools::PdfTools;
-use crate::structs::{Export, Message, Record};
+use crate::persons::Persons;
+use crate::record_date::DateFormat;
+use crate::record_field::RecordField;
+use crate::report::Report;
+use crate::sanitizer::Sanitizer;
+use crate::structs::{Export, Message, Record, TextEntity};
+use crate::tag_query::TagQuery;
 use crate::toc::{Toc, TocItem};
+use crate::volumes::{RecordPdf, Volume, VolumeSplit, VolumeSummary, Volumes};
 
 mod app;
+mod archive;
 mod categorizer;
+mod check;
 mod command;
+mod config;
+mod explore;
+mod folder_source;
+mod html_export;
+mod output;
+mod overrides;
 mod pdf_tools;
+mod persons;
+mod record_date;
+mod record_field;
+mod report;
+mod sanitizer;
 mod structs;
+mod tag_query;
 mod toc;
+mod volumes;
 
-/// Simple program to greet a person
-#[derive(Parser, Debug, Clone)]
-#[command(version, about, long_about = None)]
+/// Progress steps of a document besides its records: ToC and Unite
+const EXTRA_STEPS: u64 = 2;
+
+/// Builds PDF documents of medical records from Telegram exports and folders
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Arguments of `build` that runs if no command is given
+    #[command(flatten)]
+    build: Cli,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Build PDF documents of records (default)
+    Build(Box<Cli>),
+    /// Report broken records, missing files and orphaned documents without building
+    Check(CheckArgs),
+    /// List records of people without building
+    List(ExploreArgs),
+    /// Show numbers of records, date ranges, tags, doctors and places of people without building
+    Stats(ExploreArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct ExploreArgs {
+    /// Configuration file (medpack.toml of the current or source directories by default)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Profile of the configuration file to apply
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Print JSON instead of tables
+    #[arg(long)]
+    json: bool,
+
+    /// YAML file with record metadata overrides keyed by message id
+    #[arg(long)]
+    overrides: Option<PathBuf>,
+
+    /// YAML file with canonical person names and their aliases
+    #[arg(long)]
+    persons: Option<PathBuf>,
+
+    /// Source locations (directories or .zip/.tar.gz archives)
+    #[arg(default_values_t = vec![".".to_string()])]
+    sources: Vec<String>,
+
+    /// Show records since the date or the period back from today (10d, 3w, 6m, 2y)
+    #[arg(long)]
+    since: Option<String>,
+
+    /// Show records until the date or the period back from today (10d, 3w, 6m, 2y)
+    #[arg(long)]
+    until: Option<String>,
+
+    /// Show records with tags matching the query: `cardiology and not dental`, `ecg | holter`
+    #[arg(long)]
+    tags: Option<TagQuery>,
+
+    /// Show records of doctors containing any of the names (case-insensitive)
+    #[arg(long)]
+    doctor: Vec<String>,
+
+    /// Show records of places containing any of the names (case-insensitive)
+    #[arg(long)]
+    place: Vec<String>,
+
+    /// Match doctors and places exactly instead of by substring
+    #[arg(long)]
+    exact_match: bool,
+
+    /// Filter people to show (all by default)
+    #[arg(short = 'p')]
+    people: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct CheckArgs {
+    /// Configuration file (medpack.toml of the current or source directories by default)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// YAML file with record metadata overrides keyed by message id
+    #[arg(long)]
+    overrides: Option<PathBuf>,
+
+    /// YAML file with canonical person names and their aliases
+    #[arg(long)]
+    persons: Option<PathBuf>,
+
+    /// Source locations (directories or .zip/.tar.gz archives)
+    #[arg(default_values_t = vec![".".to_string()])]
+    sources: Vec<String>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
 struct Cli {
+    /// Configuration file (medpack.toml of the current or source directories by default)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Profile of the configuration file to apply
+    #[arg(long)]
+    profile: Option<String>,
+
     /// Preserve tmp directories
     #[arg(long)]
     preserve_tmp: bool,
@@ -47,7 +186,103 @@ struct Cli {
     #[arg(long)]
     unadaptive_text_pages: bool,
 
-    /// Source locations
+    /// List link urls of text records as footnotes for paper copies
+    #[arg(long)]
+    link_footnotes: bool,
+
+    /// Transcribe voice and audio messages with whisper.cpp using this model file
+    #[arg(long)]
+    whisper_model: Option<PathBuf>,
+
+    /// Languages of voice and audio messages. First one is used if detected isn't in the list
+    #[arg(long, default_value = "ru+en")]
+    transcribe_languages: String,
+
+    /// HTML tags allowed in `html` blocks and record tags
+    #[arg(long, value_delimiter = ',', default_values = Sanitizer::DEFAULT_TAGS)]
+    html_tags: Vec<String>,
+
+    /// CSS properties allowed in style attributes of `html` blocks and record tags
+    #[arg(long, value_delimiter = ',', default_values = Sanitizer::DEFAULT_STYLES)]
+    html_styles: Vec<String>,
+
+    /// Output format of record dates (strftime)
+    #[arg(long, default_value = DateFormat::DEFAULT_DAY)]
+    date_format: String,
+
+    /// Output format of record dates precise to a month (strftime)
+    #[arg(long, default_value = DateFormat::DEFAULT_MONTH)]
+    month_format: String,
+
+    /// YAML file with record metadata overrides keyed by message id
+    #[arg(long)]
+    overrides: Option<PathBuf>,
+
+    /// YAML file with canonical person names and their aliases
+    #[arg(long)]
+    persons: Option<PathBuf>,
+
+    /// Build records since the date (2023.05.01, 2023.05, 2023) or the period back from today (10d, 3w, 6m, 2y)
+    #[arg(long)]
+    since: Option<String>,
+
+    /// Build records until the date (2023.05.01, 2023.05, 2023) or the period back from today (10d, 3w, 6m, 2y)
+    #[arg(long)]
+    until: Option<String>,
+
+    /// Build records with tags matching the query: `cardiology and not dental`, `ecg | holter`
+    #[arg(long)]
+    tags: Option<TagQuery>,
+
+    /// Build records of doctors containing any of the names (case-insensitive)
+    #[arg(long)]
+    doctor: Vec<String>,
+
+    /// Build records of places containing any of the names (case-insensitive)
+    #[arg(long)]
+    place: Vec<String>,
+
+    /// Match doctors and places exactly instead of by substring
+    #[arg(long)]
+    exact_match: bool,
+
+    /// Split person documents into sections per doctor or place with heading pages
+    #[arg(long, value_enum)]
+    group_by: Option<RecordField>,
+
+    /// Directory of built documents (created if missing)
+    #[arg(long, default_value = ".")]
+    output_dir: PathBuf,
+
+    /// File name of person documents with {person}, {from}, {to}, {profile} and {records} placeholders
+    #[arg(long, default_value = Output::DEFAULT_TEMPLATE)]
+    output_name: String,
+
+    /// Transliterate non-Latin characters of file names (Мария → Mariia)
+    #[arg(long)]
+    transliterate: bool,
+
+    /// Overwrite existing documents
+    #[arg(long)]
+    force: bool,
+
+    /// Split person documents into volumes per calendar year or per the first tag of records
+    #[arg(long, value_enum)]
+    split_by: Option<VolumeSplit>,
+
+    /// Start a new volume when it would exceed the number of pages
+    #[arg(long)]
+    max_pages: Option<usize>,
+
+    /// Start a new volume when it would exceed the size in megabytes
+    #[arg(long)]
+    max_mb: Option<f64>,
+
+    /// Report of messages that aren't in any record with reasons (.json or .csv)
+    #[arg(long, default_value = "medpack-skipped.json")]
+    skipped_report: PathBuf,
+
+    /// Source locations (directories or .zip/.tar.gz archives)
     #[arg(default_values_t = vec![".".to_string()])]
     sources: Vec<String>,
 
@@ -57,10 +292,26 @@ struct Cli {
 }
 
 fn get_export_result(export_path: &str) -> eyre::Result<Export> {
-    let result_json = &format!("{export_path}/result.json");
+    // Messages of archives point inside the archive until files are extracted
+    if Archive::is_archive(export_path.as_ref()) {
+        return Archive::read(export_path.as_ref());
+    }
 
-    let red = String::from_utf8(fs::read(result_json)?)?;
-    let mut data: structs::Export = serde_json::from_str(&red)?;
+    let result_json = Path::new(export_path).join("result.json");
+
+    let mut data: structs::Export = if result_json.exists() {
+        let red = String::from_utf8(fs::read(result_json)?)?;
+
+        serde_json::from_str(&red)?
+    } else if HtmlExport::is_export(export_path.as_ref()) {
+        HtmlExport::read(export_path.as_ref())?
+    } else if FolderSource::is_source(export_path.as_ref())? {
+        FolderSource::read(export_path.as_ref())?
+    } else {
+        return Err(eyre::eyre!(
+            "{export_path} contains neither result.json, messages.html nor yaml sidecars"
+        ));
+    };
 
     data.messages
         .iter_mut()
@@ -70,9 +321,14 @@ fn get_export_result(export_path: &str) -> eyre::Result<Export> {
 }
 
 fn main() -> eyre::Result<()> {
-    let cli_args = Cli::parse();
+    let args = Config::parse_args()?;
 
-    let res = app(cli_args);
+    let res = match args.command.unwrap_or(Command::Build(Box::new(args.build))) {
+        Command::Build(cli) => app(*cli),
+        Command::Check(args) => Check::run(&args),
+        Command::List(args) => Explore::list(&args),
+        Command::Stats(args) => Explore::stats(&args),
+    };
 
     let Err(err) = res else { return Ok(()) };
 
@@ -104,10 +360,11 @@ fn app(args: Cli) -> eyre::Result<()> {
 
     if args.preserve_tmp {
         println!(
-            "tmp folders: {tmp_html} {tmp_img} {tmp_label}",
+            "tmp folders: {tmp_html} {tmp_img} {tmp_label} {tmp_sources}",
             tmp_html = app.tmp_html("").to_string_lossy(),
             tmp_label = app.tmp_label("").to_string_lossy(),
             tmp_img = app.tmp_img("").to_string_lossy(),
+            tmp_sources = app.tmp_sources("").to_string_lossy(),
         );
     }
 
@@ -117,14 +374,47 @@ fn app(args: Cli) -> eyre::Result<()> {
         .map(|path| get_export_result(path))
         .collect::<Result<Vec<_>, _>>()?;
 
-    let chat_id = exports.first().map(|export| export.id).unwrap_or_default();
+    // HTML exports don't contain chat id
+    let chat_id = exports
+        .iter()
+        .map(|export| export.id)
+        .find(|id| *id != 0)
+        .unwrap_or_default();
+
+    let overrides = args
+        .overrides
+        .as_deref()
+        .map(Overrides::read)
+        .transpose()?
+        .unwrap_or_default();
+
+    let persons = args
+        .persons
+        .as_deref()
+        .map(Persons::read)
+        .transpose()?
+        .unwrap_or_default();
+
+    let (mut person_records, skipped) =
+        Categorizer::process_exports(exports, &overrides, &persons)?;
 
-    let mut person_records = Categorizer::process_exports(exports);
+    persons.retain_people(&args.people, &mut person_records);
 
-    if !args.people.is_empty() {
-        person_records.retain(|name, _| args.people.contains(name));
+    app.period().retain_records(&mut person_records);
+
+    if let Some(tags) = &args.tags {
+        tags.retain_records(&mut person_records);
     }
 
+    RecordField::Doctor.retain_records(&args.doctor, args.exact_match, &mut person_records);
+    RecordField::Place.retain_records(&args.place, args.exact_match, &mut person_records);
+
+    let output_paths = app
+        .output()
+        .paths(&person_records, app.date_format(), app.volumes())?;
+
+    Archive::extract_records(&app, person_records.values_mut().flatten())?;
+
     let prefix_width = person_records
         .keys()
         .map(|name| name.chars().count())
@@ -143,12 +433,9 @@ fn app(args: Cli) -> eyre::Result<()> {
             .replace("[progress_width]", &progress_width.to_string()),
     )?;
 
-    // ToC and Unite
-    let extra_steps = 2;
-
     let pb_total = m
         .add(ProgressBar::new(
-            (records_len + person_records.len() * extra_steps) as _,
+            records_len as u64 + person_records.len() as u64 * EXTRA_STEPS,
         ))
         .with_style(pb_total_style)
         .with_prefix("total")
@@ -167,7 +454,7 @@ fn app(args: Cli) -> eyre::Result<()> {
         .into_iter()
         .map(|(person, records)| {
             let pb = m
-                .add(ProgressBar::new((records.len() + extra_steps) as _))
+                .add(ProgressBar::new(records.len() as u64 + EXTRA_STEPS))
                 .with_style(pb_style.clone())
                 .with_message("Starting")
                 .with_prefix(person.clone());
@@ -182,63 +469,357 @@ fn app(args: Cli) -> eyre::Result<()> {
         .into_par_iter()
         // .filter(|(name, _)| name == "nataly")
         // .take_any(1)
-        .map(|(name, (recs, pb))| process_person(&app, &name, chat_id, &recs, &pb, &pb_total))
+        .map(|(name, (recs, pb))| {
+            let output_path = &output_paths[&name];
+
+            process_person(&app, &name, chat_id, &recs, output_path, &pb, &pb_total)
+        })
         .collect();
 
     pb_total.finish_with_message("everything is done");
 
+    Report::write(&args.skipped_report, chat_id, &skipped)?;
+
+    if !skipped.is_empty() {
+        println!(
+            "{} messages aren't in any record, see {}:\n{}",
+            skipped.len(),
+            args.skipped_report.display(),
+            Report::summary(&skipped)
+        );
+    }
+
     result?;
 
     Ok(())
 }
 
-fn process_message(app: &App, msg: &Message, pb: &ProgressBar) -> eyre::Result<PathBuf> {
+fn process_message(
+    app: &App,
+    chat_id: i64,
+    msg: &Message,
+    pb: &ProgressBar,
+) -> eyre::Result<Vec<PathBuf>> {
     let path = if msg.is_pdf() {
         msg.unwrap_file()
+    } else if msg.is_office_document() {
+        PdfTools::from_office(app, msg.id, &msg.unwrap_file())?
     } else if msg.is_photo() {
         let path_img = app.tmp_img(format!("{}-img.pdf", msg.id));
 
-        command::img2pdf([
-            "--imgsize",
-            "595x5000",
-            "--fit",
-            "into",
-            &msg.unwrap_photo().to_string_lossy(),
-            "-o",
-            &path_img.to_string_lossy(),
-        ])?;
+        let images = if msg.needs_image_conversion() {
+            PdfTools::convert_image(app, msg.id, &msg.unwrap_photo(), msg.is_multipage_image())?
+        } else {
+            vec![msg.unwrap_photo()]
+        };
+
+        let mut args = ["--imgsize", "595x5000", "--fit", "into"]
+            .map(OsStr::new)
+            .to_vec();
+        args.extend(images.iter().map(|image| image.as_os_str()));
+        args.extend([OsStr::new("-o"), path_img.as_os_str()]);
+
+        command::img2pdf(args)?;
 
         path_img
+    } else if msg.is_media() {
+        let media = process_media(app, chat_id, msg, pb)?;
+
+        if app.cli().whisper_model.is_none() || !msg.is_audio() {
+            return Ok(vec![media]);
+        }
+
+        pb.set_message(format!("transcribe {} message", msg.id));
+
+        let transcript = TextEntity::Plain {
+            text: transcribe(app, msg)?,
+        };
+
+        let transcript = PdfTools::from_html(
+            app,
+            format!("{}-transcript", msg.id),
+            &format!("<h5>transcript</h5>{}", transcript.to_html(app.sanitizer())),
+            pb,
+        )?;
+
+        return Ok(vec![media, transcript]);
     } else {
-        let content = msg.text_entities[1..]
-            .iter()
-            .map(|entity| entity.to_html())
-            .join("");
+        let content = entities_to_html(app, msg.caption_entities());
 
         PdfTools::from_html(app, msg.id, &content, pb)?
     };
 
-    Ok(path)
+    Ok(vec![path])
+}
+
+/// Text of the message with link urls listed as footnotes if requested
+fn entities_to_html(app: &App, entities: &[TextEntity]) -> String {
+    let mut footnotes = vec![];
+
+    let content = entities
+        .iter()
+        .map(|entity| {
+            let html = entity.to_html(app.sanitizer());
+
+            match entity.href().filter(|_| app.cli().link_footnotes) {
+                Some(href) => {
+                    footnotes.push(href);
+
+                    format!("{html}<sup>[{}]</sup>", footnotes.len())
+                },
+                None => html,
+            }
+        })
+        .join("");
+
+    if footnotes.is_empty() {
+        return content;
+    }
+
+    let footnotes = footnotes
+        .iter()
+        .map(|href| {
+            let href = Sanitizer::escape(href);
+
+            format!(r#"<li><a href="{href}">{href}</a></li>"#)
+        })
+        .join("");
+
+    format!(r#"{content}<hr /><ol class="small-font">{footnotes}</ol>"#)
+}
+
+/// Speech to text of the voice or audio message with whisper.cpp
+fn transcribe(app: &App, msg: &Message) -> eyre::Result<String> {
+    let model = app.cli().whisper_model.clone().expect("Checked before");
+    let model = model.to_string_lossy();
+
+    let languages = app.cli().transcribe_languages.split('+').collect_vec();
+
+    // whisper.cpp takes only 16 kHz wav files
+    let wav = app.tmp_img(format!("{}-audio.wav", msg.id));
+
+    command::ffmpeg([
+        "-y",
+        "-loglevel",
+        "error",
+        "-i",
+        &msg.unwrap_file().to_string_lossy(),
+        "-ar",
+        "16000",
+        "-ac",
+        "1",
+        "-c:a",
+        "pcm_s16le",
+        &wav.to_string_lossy(),
+    ])?;
+
+    let wav = wav.to_string_lossy();
+
+    let language = if let [language] = languages.as_slice() {
+        language.to_string()
+    } else {
+        let detected = command::whisper(["-m", &model, "-f", &wav, "--detect-language"])?;
+        let output = detected.stdout()? + &detected.stderr()?;
+
+        let re = Regex::new(r"auto-detected language: (\w+)")?;
+
+        re.captures(&output)
+            .map(|cap| cap[1].to_string())
+            .filter(|language| languages.contains(&language.as_str()))
+            .or(languages.first().map(|language| language.to_string()))
+            .unwrap_or("auto".into())
+    };
+
+    let out = app.tmp_img(format!("{}-transcript", msg.id));
+
+    command::whisper([
+        "-m",
+        &model,
+        "-f",
+        &wav,
+        "-l",
+        &language,
+        "--no-timestamps",
+        "--output-txt",
+        "--output-file",
+        &out.to_string_lossy(),
+    ])?;
+
+    let transcript = fs::read_to_string(out.with_extension("txt"))?;
+
+    Ok(transcript.trim().to_string())
+}
+
+/// Page with replies to the record followed by their attachments
+fn process_addenda(
+    app: &App,
+    chat_id: i64,
+    rec: &Record,
+    pb: &ProgressBar,
+) -> eyre::Result<Vec<PathBuf>> {
+    let entries = rec
+        .addenda
+        .iter()
+        .map(|msg| {
+            let (_, from) = msg.sender();
+
+            format!(
+                r#"
+                <div class="mb-3">
+                    <div class="small-font">
+                        {from} {date} <a href="{link}">{link_text}</a>
+                    </div>
+                    <div>{text}</div>
+                </div>
+                "#,
+                from = Sanitizer::escape(&from.unwrap_or_default()),
+                date = msg.date.format("%d.%m.%Y %H:%M"),
+                link = Sanitizer::escape(&msg.link(chat_id)),
+                link_text = Sanitizer::escape(&msg.link_text()),
+                text = entities_to_html(app, msg.caption_entities()),
+            )
+        })
+        .join("");
+
+    let content = format!("<h4>Addenda</h4>{entries}");
+
+    let mut pdfs = vec![PdfTools::from_html(
+        app,
+        format!("{}-addenda", rec.record_id()),
+        &content,
+        pb,
+    )?];
+
+    for msg in rec.addenda.iter().filter(|msg| msg.is_attachment()) {
+        pdfs.extend(process_message(app, chat_id, msg, pb)?);
+    }
+
+    Ok(pdfs)
+}
+
+/// Placeholder page for voice, video and audio messages
+fn process_media(
+    app: &App,
+    chat_id: i64,
+    msg: &Message,
+    pb: &ProgressBar,
+) -> eyre::Result<PathBuf> {
+    let file = msg.file.as_ref().map(|_| msg.unwrap_file());
+
+    let mut images = file
+        .as_ref()
+        .filter(|file| msg.is_video() && file.exists())
+        .map(|file| {
+            PdfTools::keyframes(app, msg.id, file, msg.duration_seconds).unwrap_or_else(|err| {
+                pb.println(format!(
+                    "keyframes extraction is failed for {} message: {err}",
+                    msg.id
+                ));
+
+                vec![]
+            })
+        })
+        .unwrap_or_default();
+
+    // Thumbnail is used if there is no video file or it can't be processed
+    if images.is_empty() {
+        images.extend(
+            msg.unwrap_thumbnail()
+                .filter(|thumbnail| thumbnail.exists()),
+        );
+    }
+
+    let duration = msg
+        .duration_seconds
+        .map(|seconds| {
+            format!(
+                "{:02}:{:02}:{:02}",
+                seconds / 3600,
+                seconds % 3600 / 60,
+                seconds % 60
+            )
+        })
+        .unwrap_or_default();
+
+    let size = msg
+        .width
+        .zip(msg.height)
+        .map(|(width, height)| format!("{width}x{height}"))
+        .unwrap_or_default();
+
+    let rows = [
+        ("type", msg.media_type.clone().or(msg.mime_type.clone())),
+        (
+            "file",
+            file.as_ref()
+                .and_then(|file| file.file_name())
+                .map(|name| name.to_string_lossy().to_string()),
+        ),
+        ("duration", Some(duration)),
+        ("size", Some(size)),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Some((name, value.filter(|value| !value.is_empty())?)))
+    .map(|(name, value)| {
+        format!(
+            "<tr><th>{name}</th><td>{}</td></tr>",
+            Sanitizer::escape(&value)
+        )
+    })
+    .join("");
+
+    let images = images
+        .iter()
+        .map(|image| {
+            format!(
+                r#"<img src="file://{}" style="max-width: 48%; margin: 1%" />"#,
+                Sanitizer::escape(&image.to_string_lossy())
+            )
+        })
+        .join("");
+
+    let caption = entities_to_html(app, msg.caption_entities());
+
+    let content = format!(
+        r#"
+        <table class="table table-sm" style="width: inherit">
+            {rows}
+            <tr><th>message</th><td><a href="{link}">{link}</a></td></tr>
+        </table>
+        <div>{images}</div>
+        <div>{caption}</div>
+        "#,
+        link = Sanitizer::escape(&msg.link(chat_id)),
+    );
+
+    PdfTools::from_media_html(app, format!("{}-media", msg.id), &content, pb)
 }
 
-fn process_record<'a>(
+/// Record pdf with the number of its pages
+fn process_record(
     app: &App,
     chat_id: i64,
-    rec: &'a Record,
+    rec: &Record,
     pb: &ProgressBar,
-) -> eyre::Result<(PathBuf, TocItem<'a>)> {
+) -> eyre::Result<(PathBuf, usize)> {
     let mut pdfs = vec![];
 
     for msg in &rec.messages {
         pb.set_message(format!("process {} message", msg.id));
 
-        let pdf = process_message(app, msg, pb)?;
+        let pdf = process_message(app, chat_id, msg, pb)?;
 
-        pdfs.push(pdf);
+        pdfs.extend(pdf);
 
         pb.set_message(format!("complete {} message", msg.id));
     }
 
+    if !rec.addenda.is_empty() {
+        pb.set_message(format!("process addenda for {} record", rec.record_id()));
+
+        pdfs.extend(process_addenda(app, chat_id, rec, pb)?);
+    }
+
     let record_pdf = if pdfs.len() == 1 {
         pdfs.first().cloned().expect("Should be one")
     } else {
@@ -310,7 +891,7 @@ fn process_record<'a>(
     if tags.chars().count() > 58 {
         tags = format!("{}...", tags.chars().take(55).collect::<String>());
     }
-    let label = format!("{}: {}", tags, &rec.date);
+    let label = format!("{}: {}", tags, app.date_format().format(&rec.date));
 
     let paging = "стр %Page из %EndPage".to_string();
 
@@ -320,21 +901,16 @@ fn process_record<'a>(
         &labeled_pdf,
         &paging,
         &label,
-        &rec.first_message_id().to_string(),
-        &format!("https://t.me/c/{chat_id}/{id}", id = rec.first_message_id()),
+        &rec.link_text(),
+        &rec.link(chat_id),
     )?;
 
     let pages = PdfTools::get_pages_count(&labeled_pdf)?;
 
-    Ok((labeled_pdf, TocItem { record: rec, pages }))
+    Ok((labeled_pdf, pages))
 }
 
-fn generate_toc_file(
-    app: &App,
-    person_name: &str,
-    toc: Toc,
-    pb: &ProgressBar,
-) -> eyre::Result<PathBuf> {
+fn generate_toc_file(app: &App, slug: &str, toc: Toc, pb: &ProgressBar) -> eyre::Result<PathBuf> {
     let mut shift = 1;
 
     let mut output_path = "".into();
@@ -344,8 +920,8 @@ fn generate_toc_file(
     for _ in ["first", "second"] {
         output_path = PdfTools::from_html(
             app,
-            "toc-".to_string() + person_name,
-            &toc.generate_html(shift),
+            "toc-".to_string() + slug,
+            &toc.generate_html(shift, app.sanitizer(), app.date_format()),
             pb,
         )?;
 
@@ -359,11 +935,38 @@ fn generate_toc_file(
     Ok(output_path)
 }
 
+/// Heading page of a doctor or place section
+fn generate_section_file(
+    app: &App,
+    person_name: &str,
+    slug: &str,
+    index: usize,
+    title: &str,
+    records: usize,
+    pb: &ProgressBar,
+) -> eyre::Result<PathBuf> {
+    pb.set_message(format!("generate {title} section heading"));
+
+    let content = format!(
+        r#"
+        <div style="text-align: center; margin-top: 30%">
+            <h1>{title}</h1>
+            <p>{person}: {records} records</p>
+        </div>
+        "#,
+        title = Sanitizer::escape(title),
+        person = Sanitizer::escape(person_name),
+    );
+
+    PdfTools::from_html(app, format!("section-{slug}-{index}"), &content, pb)
+}
+
 fn process_person(
     app: &App,
     name: &str,
     chat_id: i64,
     recs: &[Record],
+    output_path: &Path,
     pb: &ProgressBar,
     pb_total: &ProgressBar,
 ) -> eyre::Result<()> {
@@ -372,7 +975,15 @@ fn process_person(
         .map(|rec| {
             pb.set_message(format!("process {} record", rec.record_id()));
 
-            let res = process_record(app, chat_id, rec, pb);
+            let res = app
+                .process_record_once(rec.record_id(), || process_record(app, chat_id, rec, pb))
+                .and_then(|(path, pages)| {
+                    Ok(RecordPdf {
+                        bytes: fs::metadata(&path)?.len(),
+                        path,
+                        item: TocItem { record: rec, pages },
+                    })
+                });
 
             pb.inc(1);
             pb_total.inc(1);
@@ -383,35 +994,213 @@ fn process_person(
         })
         .collect::<Result<Vec<_>, _>>()?;
 
-    let (mut pdfs, toc_items): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+    if !app.volumes().is_enabled() {
+        build_document(app, name, chat_id, results, None, output_path, pb)?;
 
-    let mut toc = Toc::new(chat_id);
-    toc.append(toc_items);
+        pb_total.inc(EXTRA_STEPS);
+
+        pb.finish_with_message(format!("finished - result file {}", output_path.display()));
+
+        return Ok(());
+    }
+
+    let volumes = split_volumes(app, name, chat_id, results, pb)?;
+
+    // ToC and unite of the other volumes and the index
+    let extra_volume_steps = (volumes.len() as u64 - 1) * EXTRA_STEPS + 1;
+
+    pb.inc_length(extra_volume_steps);
+    pb_total.inc_length(extra_volume_steps);
+
+    let mut summaries = vec![];
+
+    for (index, volume) in volumes.into_iter().enumerate() {
+        let volume_path = app.output().volume_path(output_path, &volume.name);
+        let records = volume
+            .records
+            .iter()
+            .map(|record| record.item.record)
+            .collect();
+
+        let pages = build_document(
+            app,
+            name,
+            chat_id,
+            volume.records,
+            Some((index, &volume.name)),
+            &volume_path,
+            pb,
+        )?;
 
-    let toc_path = generate_toc_file(app, name, toc, pb)?;
+        pb_total.inc(EXTRA_STEPS);
+
+        summaries.push(VolumeSummary {
+            file_name: volume_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            records,
+            pages,
+        });
+    }
+
+    pb.set_message(format!("generate index of {} volumes", summaries.len()));
+
+    let index_pdf = PdfTools::from_html(
+        app,
+        format!("index-{name}"),
+        &Volumes::index_html(name, &summaries, app.date_format()),
+        pb,
+    )?;
+
+    fs::copy(index_pdf, output_path)?;
 
     pb.inc(1);
     pb_total.inc(1);
 
+    pb.finish_with_message(format!(
+        "finished - index file {} of {} volumes",
+        output_path.display(),
+        summaries.len()
+    ));
+
+    Ok(())
+}
+
+/// Volumes within the page limit. Tables of contents are reserved one page
+/// and regenerated if they turn out longer in volumes of several records
+fn split_volumes<'a>(
+    app: &App,
+    name: &str,
+    chat_id: i64,
+    records: Vec<RecordPdf<'a>>,
+    pb: &ProgressBar,
+) -> eyre::Result<Vec<Volume<'a>>> {
+    let mut toc_pages = 1;
+
+    loop {
+        let volumes = app.volumes().split(records.clone(), toc_pages);
+
+        // Adaptive table of contents is always a single page
+        if app.volumes().max_pages().is_none() || !app.cli().unadaptive_text_pages {
+            return Ok(volumes);
+        }
+
+        let mut required_toc_pages = toc_pages;
+
+        for (index, volume) in volumes.iter().enumerate() {
+            if volume.records.len() < 2 {
+                continue;
+            }
+
+            pb.set_message(format!("check toc pages of {} volume", volume.name));
+
+            let toc = document_toc(app, chat_id, &volume.records, Some(&volume.name));
+            let toc_path = generate_toc_file(app, &format!("{name}-{index}"), toc, pb)?;
+
+            required_toc_pages = required_toc_pages.max(PdfTools::get_pages_count(&toc_path)?);
+        }
+
+        if required_toc_pages == toc_pages {
+            return Ok(volumes);
+        }
+
+        toc_pages = required_toc_pages;
+    }
+}
+
+/// Table of contents of records, in sections if records are grouped
+fn document_toc<'a>(
+    app: &App,
+    chat_id: i64,
+    records: &[RecordPdf<'a>],
+    volume: Option<&str>,
+) -> Toc<'a> {
+    let mut toc = Toc::new(chat_id);
+    let items = records
+        .iter()
+        .map(|record| record.item.clone())
+        .collect_vec();
+
+    match app.cli().group_by {
+        Some(field) => {
+            for (title, items) in field.sections(items, |item| item.record) {
+                toc.append_section(Some(title), items);
+            }
+        },
+        None => toc.append(items),
+    }
+
+    if app.period().is_bounded() {
+        toc.period = Some(app.period().format(app.date_format()));
+    }
+
+    toc.volume = volume.map(ToString::to_string);
+
+    toc
+}
+
+/// Person document or its volume with the table of contents.
+/// Returns the number of pages
+fn build_document(
+    app: &App,
+    name: &str,
+    chat_id: i64,
+    records: Vec<RecordPdf>,
+    volume: Option<(usize, &str)>,
+    output_path: &Path,
+    pb: &ProgressBar,
+) -> eyre::Result<usize> {
+    // Volumes have their own temporary files
+    let slug = match volume {
+        Some((index, _)) => format!("{name}-{index}"),
+        None => name.to_string(),
+    };
+
+    let mut toc = document_toc(app, chat_id, &records, volume.map(|(_, volume)| volume));
+
+    let mut pdfs = vec![];
+    let mut pages = records.iter().map(|record| record.item.pages).sum();
+
+    if let Some(field) = app.cli().group_by {
+        let sections = field.sections(records, |record| record.item.record);
+
+        for (index, (title, section)) in sections.into_iter().enumerate() {
+            let heading_path =
+                generate_section_file(app, name, &slug, index, &title, section.len(), pb)?;
+
+            // Sections of the table of contents are in the same order
+            toc.sections[index].heading_pages = PdfTools::get_pages_count(&heading_path)?;
+            pages += toc.sections[index].heading_pages;
+
+            pdfs.push(heading_path);
+            pdfs.extend(section.into_iter().map(|record| record.path));
+        }
+    } else {
+        pdfs.extend(records.into_iter().map(|record| record.path));
+    }
+
+    let toc_path = generate_toc_file(app, &slug, toc, pb)?;
+
+    pages += PdfTools::get_pages_count(&toc_path)?;
+
+    pb.inc(1);
+
     pdfs.insert(0, toc_path);
 
     pb.set_message(format!("unite {} pdf files", pdfs.len()));
 
     // Output file as last parameter
-    let united_pdf = app.tmp_label(format!("{name}.pdf"));
+    let united_pdf = app.tmp_label(format!("{slug}.pdf"));
 
     pdfs.push(united_pdf.clone());
 
     command::pdfunite(pdfs)?;
 
     pb.inc(1);
-    pb_total.inc(1);
-
-    let result_pds = format!("{name}.pdf");
 
-    PdfTools::add_page_numbers(&united_pdf, result_pds.as_ref())?;
+    PdfTools::add_page_numbers(&united_pdf, output_path)?;
 
-    pb.finish_with_message(format!("finished - result file {result_pds}"));
-
-    Ok(())
+    Ok(pages)
 }
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use eyre::eyre;
+use regex::Regex;
+
+use crate::Cli;
+use crate::categorizer::PersonRecords;
+use crate::record_date::{DateFormat, RecordDate};
+use crate::structs::Record;
+use crate::volumes::Volumes;
+
+/// Locations of person documents built from the file-name template:
+/// `{person}_{from}-{to}_{profile}.pdf`
+#[derive(Debug, Clone)]
+pub struct Output {
+    dir: PathBuf,
+    template: String,
+    profile: Option<String>,
+    transliterate: bool,
+    force: bool,
+}
+
+impl Output {
+    pub const DEFAULT_TEMPLATE: &str = "{person}.pdf";
+    const PLACEHOLDERS: [&str; 5] = ["person", "from", "to", "profile", "records"];
+
+    pub fn new(cli: &Cli) -> eyre::Result<Self> {
+        for placeholder in Self::placeholder_regex().captures_iter(&cli.output_name) {
+            if !Self::PLACEHOLDERS.contains(&&placeholder[1]) {
+                return Err(eyre!(
+                    "unknown placeholder {{{}}} of the output name, expected one of: {}",
+                    &placeholder[1],
+                    Self::PLACEHOLDERS.join(", ")
+                ));
+            }
+        }
+
+        Ok(Self {
+            dir: cli.output_dir.clone(),
+            template: cli.output_name.clone(),
+            profile: cli.profile.clone(),
+            transliterate: cli.transliterate,
+            force: cli.force,
+        })
+    }
+
+    /// Document paths of people. Fails before anything is built if a document
+    /// or any of its possible volumes exists and overwriting isn't forced
+    /// or if several people share a path
+    pub fn paths(
+        &self,
+        person_records: &PersonRecords,
+        date_format: &DateFormat,
+        volumes: &Volumes,
+    ) -> eyre::Result<HashMap<String, PathBuf>> {
+        let mut paths = HashMap::new();
+        let mut people = HashMap::new();
+
+        for (person, records) in person_records {
+            let path = self.dir.join(self.file_name(person, records, date_format));
+
+            if let Some(other) = people.insert(path.clone(), person) {
+                return Err(eyre!(
+                    "documents of {other} and {person} have the same path {}, add {{person}} to the output name",
+                    path.display()
+                ));
+            }
+
+            self.check_overwrite(&path)?;
+
+            if volumes.is_enabled() {
+                for volume in volumes.possible_names(records) {
+                    self.check_overwrite(&self.volume_path(&path, &volume))?;
+                }
+            }
+
+            paths.insert(person.clone(), path);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+
+        Ok(paths)
+    }
+
+    /// Path of a volume next to the index document of the person: `maria_2023.pdf`
+    pub fn volume_path(&self, path: &Path, volume: &str) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+        path.with_file_name(self.safe_file_name(&format!("{stem}_{volume}")))
+    }
+
+    fn check_overwrite(&self, path: &Path) -> eyre::Result<()> {
+        if path.exists() && !self.force {
+            return Err(eyre!(
+                "{} already exists, use --force to overwrite it",
+                path.display()
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn file_name(&self, person: &str, records: &[Record], date_format: &DateFormat) -> String {
+        let span = RecordDate::span(records.iter().map(|rec| &rec.date));
+        let from = span.as_ref().map(|span| span.start);
+        let to = span.as_ref().map(|span| span.end.unwrap_or(span.start));
+
+        let name =
+            Self::placeholder_regex().replace_all(&self.template, |caps: &regex::Captures| {
+                match &caps[1] {
+                    "person" => person.to_string(),
+                    "from" => from
+                        .map(|date| date_format.format_partial(&date))
+                        .unwrap_or_default(),
+                    "to" => to
+                        .map(|date| date_format.format_partial(&date))
+                        .unwrap_or_default(),
+                    "profile" => self.profile.clone().unwrap_or_default(),
+                    "records" => records.len().to_string(),
+                    _ => unreachable!("Placeholders are validated"),
+                }
+            });
+
+        self.safe_file_name(&name)
+    }
+
+    /// File name without path separators, quotes and characters reserved on Windows,
+    /// and without separators left around empty placeholders
+    fn safe_file_name(&self, name: &str) -> String {
+        let name = if self.transliterate {
+            deunicode::deunicode(name)
+        } else {
+            name.to_string()
+        };
+
+        let name = name
+            .chars()
+            .map(|char| {
+                if char.is_control() || r#"/\:*?"'`<>|"#.contains(char) {
+                    '_'
+                } else {
+                    char
+                }
+            })
+            .collect::<String>();
+
+        let stem = match name.len().checked_sub(4) {
+            Some(index)
+                if name.is_char_boundary(index) && name[index..].eq_ignore_ascii_case(".pdf") =>
+            {
+                &name[..index]
+            },
+            _ => name.as_str(),
+        };
+
+        let stem = Regex::new(r"_{2,}")
+            .expect("Valid regex")
+            .replace_all(stem, "_");
+
+        let stem = stem.trim_matches(|char: char| "_- .".contains(char));
+
+        let stem = if stem.is_empty() { "unnamed" } else { stem };
+
+        format!("{stem}.pdf")
+    }
+
+    fn placeholder_regex() -> Regex {
+        Regex::new(r"\{(\w*)\}").expect("Valid regex")
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use eyre::eyre;
+use serde::Deserialize;
+
+use crate::record_date::RecordDate;
+use crate::structs::{Message, Record, TextEntity};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Override {
+    /// Drop the message with its record, the record continuation and comments
+    #[serde(default)]
+    exclude: bool,
+    /// Message ids to add to the record of the message
+    #[serde(default)]
+    attach: Vec<i64>,
+    /// Record fields to set or replace
+    #[serde(flatten)]
+    fields: serde_yaml::Mapping,
+}
+
+/// Local patches of record metadata keyed by message id for messages
+/// that can't be edited in Telegram.
+///
+/// Overrides are applied to messages before grouping, so fields end up
+/// in the record block of the message as if it was written in the chat
+#[derive(Debug, Clone, Default)]
+pub struct Overrides(HashMap<i64, Override>);
+
+impl Overrides {
+    pub fn read(path: &Path) -> eyre::Result<Self> {
+        let overrides: HashMap<i64, Override> =
+            serde_yaml::from_str(&RecordDate::quote_yaml(&fs::read_to_string(path)?))
+                .map_err(|err| eyre!("{}: {err}", path.display()))?;
+
+        for (id, item) in &overrides {
+            for key in item.fields.keys() {
+                let key = key.as_str().unwrap_or_default();
+
+                if !Record::FIELDS.contains(&key) {
+                    return Err(eyre!(
+                        "{}: unknown field `{key}` of {id} message",
+                        path.display()
+                    ));
+                }
+            }
+        }
+
+        Ok(Self(overrides))
+    }
+
+    pub fn apply(&self, messages: Vec<Message>) -> eyre::Result<Vec<Message>> {
+        if self.0.is_empty() {
+            return Ok(messages);
+        }
+
+        for id in self.0.keys() {
+            if !messages.iter().any(|msg| msg.id == *id) {
+                crate::write_err(format!("overridden message {id} isn't in sources")).ok();
+            }
+        }
+
+        // Attached message id to the record message id
+        let attached: HashMap<i64, i64> = self
+            .0
+            .iter()
+            .filter(|(_, item)| !item.exclude)
+            .flat_map(|(id, item)| item.attach.iter().map(|attached| (*attached, *id)))
+            .collect();
+
+        let mut result = vec![];
+
+        for mut msg in messages {
+            let item = self.0.get(&msg.id).cloned().unwrap_or_default();
+
+            // Excluded record is grouped as usual to take its continuation with it
+            if item.exclude {
+                msg.excluded = true;
+                result.push(msg);
+
+                continue;
+            }
+
+            if !item.fields.is_empty() {
+                Self::set_fields(&mut msg, item.fields)?;
+            }
+
+            if let Some(&record_id) = attached.get(&msg.id) {
+                // Reply keeps the message in the topic of the record
+                msg.part_of = Some(record_id);
+                msg.reply_to_message_id = Some(record_id);
+            }
+
+            result.push(msg);
+        }
+
+        Ok(result)
+    }
+
+    /// Merges fields into the record block of the message or adds a new one
+    fn set_fields(msg: &mut Message, fields: serde_yaml::Mapping) -> eyre::Result<()> {
+        let block = match msg.text_entities.first() {
+            Some(TextEntity::Pre { text, language })
+                if !matches!(language.as_str(), "html" | "csv") =>
+            {
+                serde_yaml::from_str::<serde_yaml::Mapping>(&RecordDate::quote_yaml(text)).ok()
+            },
+            _ => None,
+        };
+
+        let has_block = block.is_some();
+        let mut mapping = block.unwrap_or_default();
+
+        mapping.extend(fields);
+
+        let text = serde_yaml::to_string(&mapping)?;
+
+        serde_yaml::from_str::<Record>(&text)
+            .map_err(|err| eyre!("override of {} message isn't a valid record: {err}", msg.id))?;
+
+        let entity = TextEntity::Pre {
+            text,
+            language: String::new(),
+        };
+
+        if has_block {
+            msg.text_entities[0] = entity;
+        } else {
+            msg.text_entities.insert(0, entity);
+        }
+
+        Ok(())
+    }
+}
@@ -17,6 +17,27 @@ impl PdfTools {
         slug: impl Display,
         content: &str,
         pb: &ProgressBar,
+    ) -> eyre::Result<PathBuf> {
+        Self::render_html(app, slug, content, false, pb)
+    }
+
+    /// Media placeholder page with keyframes and thumbnails from local files.
+    /// Other pages can't read local files as they contain HTML of messages
+    pub fn from_media_html(
+        app: &App,
+        slug: impl Display,
+        content: &str,
+        pb: &ProgressBar,
+    ) -> eyre::Result<PathBuf> {
+        Self::render_html(app, slug, content, true, pb)
+    }
+
+    fn render_html(
+        app: &App,
+        slug: impl Display,
+        content: &str,
+        local_file_access: bool,
+        pb: &ProgressBar,
     ) -> eyre::Result<PathBuf> {
         let bootstrap = include_str!("assets/bootstrap-v4.6.2.min.css");
 
@@ -66,11 +87,18 @@ impl PdfTools {
 
         fs::write(&path, content).expect("Should have been able to read the file");
 
+        let file_access = if local_file_access {
+            "--enable-local-file-access"
+        } else {
+            "--disable-local-file-access"
+        };
+
         let generate_file = |height: u64, margin: u64| {
             command::wkhtmltopdf(
                 &[
                     "--encoding",
                     "utf-8",
+                    file_access,
                     "--zoom",
                     "1.4",
                     "--dpi",
@@ -147,6 +175,114 @@ impl PdfTools {
         Ok(output_path)
     }
 
+    /// Converts office document (DOCX, ODT, XLSX, etc.) to pdf with headless LibreOffice
+    pub fn from_office(app: &App, slug: impl Display, in_path: &Path) -> eyre::Result<PathBuf> {
+        let out_dir = app.tmp_img(format!("{slug}-office"));
+
+        // LibreOffice refuses to run in parallel with the same profile
+        let profile = app.tmp_img(format!("{slug}-office-profile"));
+
+        command::soffice([
+            &format!("-env:UserInstallation=file://{}", profile.to_string_lossy()),
+            "--headless",
+            "--convert-to",
+            "pdf",
+            "--outdir",
+            &out_dir.to_string_lossy(),
+            &in_path.to_string_lossy(),
+        ])?;
+
+        let stem = in_path
+            .file_stem()
+            .ok_or_else(|| eyre::eyre!("{} has no file name", in_path.display()))?;
+
+        let mut output_path = out_dir.join(stem);
+        output_path.set_extension("pdf");
+
+        Ok(output_path)
+    }
+
+    /// Extracts a few evenly spaced frames of the video to jpeg files
+    pub fn keyframes(
+        app: &App,
+        slug: impl Display,
+        in_path: &Path,
+        duration_seconds: Option<i64>,
+    ) -> eyre::Result<Vec<PathBuf>> {
+        // Durations are rounded, so frames are taken within a second less
+        // not to seek past the end of the video
+        let span = duration_seconds
+            .unwrap_or_default()
+            .saturating_sub(1)
+            .max(0);
+        let count = if span > 0 { 4 } else { 1 };
+
+        let mut frames = vec![];
+
+        for index in 0..count {
+            let position = span as f64 * (index as f64 + 0.5) / count as f64;
+
+            let frame = app.tmp_img(format!("{slug}-keyframe-{index}.jpg"));
+
+            command::ffmpeg([
+                "-y",
+                "-loglevel",
+                "error",
+                "-ss",
+                &format!("{position:.2}"),
+                "-i",
+                &in_path.to_string_lossy(),
+                "-frames:v",
+                "1",
+                &frame.to_string_lossy(),
+            ])?;
+
+            // ffmpeg writes nothing if the position is still past the end
+            if frame.exists() {
+                frames.push(frame);
+            }
+        }
+
+        Ok(frames)
+    }
+
+    /// Converts image to png files `img2pdf` is able to take.
+    /// Only the first frame is taken unless the image is multipage (e.g. TIFF scans)
+    pub fn convert_image(
+        app: &App,
+        slug: impl Display,
+        in_path: &Path,
+        multipage: bool,
+    ) -> eyre::Result<Vec<PathBuf>> {
+        let prefix = format!("{slug}-frame-");
+
+        let input = if multipage {
+            in_path.to_string_lossy().to_string()
+        } else {
+            format!("{}[0]", in_path.to_string_lossy())
+        };
+
+        command::convert([
+            &input,
+            "-auto-orient",
+            &app.tmp_img(format!("{prefix}%03d.png")).to_string_lossy(),
+        ])?;
+
+        let mut frames = vec![];
+
+        for entry in fs::read_dir(app.tmp_img(""))? {
+            let entry = entry?;
+
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                frames.push(entry.path());
+            }
+        }
+
+        frames.sort();
+
+        Ok(frames)
+    }
+
     pub fn add_page_numbers(in_path: &Path, out_path: &Path) -> eyre::Result<PathBuf> {
         let font_path = ROBOTO_FONT_FILE.to_str().unwrap();
         let font = format!("Roboto={font_path}");
@@ -198,7 +334,7 @@ impl PdfTools {
         Ok(out_path.to_path_buf())
     }
 
-    pub fn get_pages_count(path: &PathBuf) -> eyre::Result<u8> {
+    pub fn get_pages_count(path: &PathBuf) -> eyre::Result<usize> {
         let out = command::pdf_info(path)?.stdout()?;
         let re = Regex::new(r"(?m)^Pages:\s+(\d+)$")?;
 
@@ -0,0 +1,178 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::{fs, iter};
+
+use eyre::eyre;
+use icu_normalizer::ComposingNormalizerBorrowed;
+use itertools::Itertools;
+
+use crate::categorizer::PersonRecords;
+use crate::structs::Record;
+
+/// Registry of people with canonical names and their aliases:
+///
+/// ```yaml
+/// maria: [Маша, Мария, Maria]
+/// ```
+///
+/// Names are compared after Unicode (NFKC), case and whitespace normalisation
+#[derive(Debug, Clone, Default)]
+pub struct Persons {
+    /// Normalised canonical name or alias to the canonical name
+    names: HashMap<String, String>,
+}
+
+impl Persons {
+    pub fn read(path: &Path) -> eyre::Result<Self> {
+        let registry: HashMap<String, Vec<String>> =
+            serde_yaml::from_str(&fs::read_to_string(path)?)
+                .map_err(|err| eyre!("{}: {err}", path.display()))?;
+
+        let mut names = HashMap::new();
+
+        for (canonical, aliases) in &registry {
+            for name in iter::once(canonical).chain(aliases) {
+                let previous = names.insert(Self::normalize(name), canonical.clone());
+
+                if let Some(previous) = previous.filter(|previous| previous != canonical) {
+                    return Err(eyre!(
+                        "{}: {name} is a name of both {previous} and {canonical}",
+                        path.display()
+                    ));
+                }
+            }
+        }
+
+        Ok(Self { names })
+    }
+
+    pub fn normalize(name: &str) -> String {
+        ComposingNormalizerBorrowed::new_nfkc()
+            .normalize(name)
+            .split_whitespace()
+            .join(" ")
+            .to_lowercase()
+    }
+
+    /// Canonical name of the person or the name itself if it isn't registered
+    pub fn canonical(&self, name: &str) -> String {
+        self.names
+            .get(&Self::normalize(name))
+            .cloned()
+            .unwrap_or_else(|| name.split_whitespace().join(" "))
+    }
+
+    /// Replaces person names of records with canonical ones
+    /// and warns about persons missing in the registry.
+    /// Spellings of a person missing in the registry become the one
+    /// of the earliest record, so `Nataly` and `nataly` share a document
+    pub fn canonicalize(&self, records: &mut [Record]) {
+        let mut unknown = BTreeSet::new();
+        let mut spellings = HashMap::new();
+
+        let persons = records
+            .iter()
+            .sorted_by_key(|rec| (rec.date.clone(), rec.messages.first().map(|msg| msg.id)))
+            .flat_map(|rec| &rec.person);
+
+        for person in persons {
+            let normalized = Self::normalize(person);
+
+            if !self.names.contains_key(&normalized) {
+                spellings
+                    .entry(normalized)
+                    .or_insert_with(|| self.canonical(person));
+            }
+        }
+
+        for rec in records {
+            for person in &rec.person {
+                if !self.names.is_empty() && !self.names.contains_key(&Self::normalize(person)) {
+                    unknown.insert(person.clone());
+                }
+            }
+
+            // Different spellings of a shared record's person become one
+            rec.person = rec
+                .person
+                .iter()
+                .map(|person| {
+                    spellings
+                        .get(&Self::normalize(person))
+                        .cloned()
+                        .unwrap_or_else(|| self.canonical(person))
+                })
+                .unique()
+                .collect();
+        }
+
+        for name in unknown {
+            let matches = Self::close_matches(&name, self.names.values().map(String::as_str));
+
+            Self::warn_unknown(&name, &matches);
+        }
+    }
+
+    /// Keeps records of the people only (all if there are no people)
+    /// and warns about the people without records
+    pub fn retain_people(&self, people: &[String], person_records: &mut PersonRecords) {
+        if people.is_empty() {
+            return;
+        }
+
+        // Documents of people missing in the registry keep the spelling of records
+        let canonical = people
+            .iter()
+            .map(|name| Self::normalize(&self.canonical(name)))
+            .collect_vec();
+
+        let found = person_records
+            .keys()
+            .map(|name| Self::normalize(name))
+            .collect_vec();
+
+        for (name, canonical) in people.iter().zip(&canonical) {
+            if !found.contains(canonical) {
+                let matches = Self::close_matches(name, person_records.keys().map(String::as_str));
+
+                Self::warn_unknown(name, &matches);
+            }
+        }
+
+        person_records.retain(|name, _| canonical.contains(&Self::normalize(name)));
+    }
+
+    /// Candidates similar to the name, the most similar first
+    pub fn close_matches<'a>(
+        name: &str,
+        candidates: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<&'a str> {
+        let name = Self::normalize(name);
+
+        candidates
+            .into_iter()
+            .unique()
+            .map(|candidate| {
+                let similarity = strsim::jaro_winkler(&name, &Self::normalize(candidate));
+
+                (candidate, similarity)
+            })
+            .filter(|(_, similarity)| *similarity >= 0.8)
+            .sorted_by(|(_, a), (_, b)| b.total_cmp(a))
+            .map(|(candidate, _)| candidate)
+            .collect()
+    }
+
+    pub fn warn_unknown(name: &str, matches: &[&str]) {
+        let message = if matches.is_empty() {
+            format!("unknown person {name}")
+        } else {
+            format!(
+                "unknown person {name}, close matches: {}",
+                matches.join(", ")
+            )
+        };
+
+        crate::write_err(message).ok();
+    }
+}
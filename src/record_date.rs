@@ -0,0 +1,480 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Write};
+use std::sync::LazyLock;
+
+use chrono::{Datelike, NaiveDate};
+use eyre::eyre;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::categorizer::{Categorizer, PersonRecords};
+
+const DAY_FORMATS: [&str; 6] = [
+    "%Y.%m.%d", "%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y", "%d-%m-%Y", "%d/%m/%Y",
+];
+const MONTH_FORMATS: [&str; 6] = ["%Y.%m", "%Y-%m", "%Y/%m", "%m.%Y", "%m-%Y", "%m/%Y"];
+const RANGE_SEPARATORS: [&str; 6] = ["..", " - ", " – ", " — ", "–", "—"];
+
+/// Dates with a 4-digit year first or last, so `22.12.23` isn't read as the year 22
+static DATE_SHAPE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\d{4}([./-]\d{1,2}){0,2}|(\d{1,2}[./-]){1,2}\d{4})$").expect("Valid regex")
+});
+
+/// Unquoted month dates like `2023.10` that YAML reads as numbers
+static NUMBER_DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^(\s*(?:-\s+)?date:[ \t]*)(\d+\.\d+)([ \t]*(?:#.*)?)$").expect("Valid regex")
+});
+
+/// Date with optional precision: a day, a month or a whole year
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartialDate {
+    Day(NaiveDate),
+    /// First day of the month
+    Month(NaiveDate),
+    Year(i32),
+}
+
+impl PartialDate {
+    pub fn parse(text: &str) -> eyre::Result<Self> {
+        let text = text.trim();
+
+        if !DATE_SHAPE.is_match(text) {
+            return Err(eyre!("Unknown date format: {text}"));
+        }
+
+        if text.len() == 4 && text.chars().all(|char| char.is_ascii_digit()) {
+            return Ok(Self::Year(text.parse()?));
+        }
+
+        if let Some(date) = DAY_FORMATS
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+        {
+            return Ok(Self::Day(date));
+        }
+
+        MONTH_FORMATS
+            .iter()
+            .find_map(|format| {
+                NaiveDate::parse_from_str(&format!("{text}|1"), &format!("{format}|%d")).ok()
+            })
+            .map(Self::Month)
+            .ok_or_else(|| eyre!("Unknown date format: {text}"))
+    }
+
+    pub fn first_day(&self) -> NaiveDate {
+        match self {
+            Self::Day(date) | Self::Month(date) => *date,
+            Self::Year(year) => NaiveDate::from_ymd_opt(*year, 1, 1).unwrap_or_default(),
+        }
+    }
+
+    pub fn last_day(&self) -> NaiveDate {
+        match self {
+            Self::Day(date) => *date,
+            Self::Month(date) => date
+                .checked_add_months(chrono::Months::new(1))
+                .and_then(|date| date.pred_opt())
+                .unwrap_or(*date),
+            Self::Year(year) => NaiveDate::from_ymd_opt(*year, 12, 31).unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawDate {
+    // YAML reads a bare year as a number
+    Year(i32),
+    Text(String),
+}
+
+/// Date of a record: a single (partial) date or a range like a hospital stay.
+///
+/// Accepts `2023.12.22`, `2023-12-22`, `22.12.2023`, `2023.12`, `12.2023`, `2023`
+/// and ranges of them separated with `..` or a dash surrounded by spaces
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawDate", into = "String")]
+pub struct RecordDate {
+    pub start: PartialDate,
+    pub end: Option<PartialDate>,
+}
+
+impl RecordDate {
+    pub fn parse(text: &str) -> eyre::Result<Self> {
+        let range = RANGE_SEPARATORS
+            .iter()
+            .find_map(|separator| text.split_once(separator));
+
+        let date = match range {
+            Some((start, end)) => Self {
+                start: PartialDate::parse(start)?,
+                end: Some(PartialDate::parse(end)?),
+            },
+            None => Self {
+                start: PartialDate::parse(text)?,
+                end: None,
+            },
+        };
+
+        if date.first_day() > date.last_day() {
+            return Err(eyre!("Date range ends before it starts: {text}"));
+        }
+
+        Ok(date)
+    }
+
+    /// Quotes month dates of YAML records as `date: 2023.10` would be read
+    /// as the number 2023.1 and lose the month
+    pub fn quote_yaml(yaml: &str) -> Cow<'_, str> {
+        NUMBER_DATE.replace_all(yaml, r#"${1}"${2}"${3}"#)
+    }
+
+    pub fn first_day(&self) -> NaiveDate {
+        self.start.first_day()
+    }
+
+    pub fn last_day(&self) -> NaiveDate {
+        self.end.unwrap_or(self.start).last_day()
+    }
+
+    /// Range from the earliest start to the latest end of the dates
+    pub fn span<'a>(dates: impl IntoIterator<Item = &'a Self> + Clone) -> Option<Self> {
+        let start = dates
+            .clone()
+            .into_iter()
+            .map(|date| date.start)
+            .min_by_key(PartialDate::first_day)?;
+
+        let end = dates
+            .into_iter()
+            .map(|date| date.end.unwrap_or(date.start))
+            .max_by_key(PartialDate::last_day)?;
+
+        Some(Self {
+            start,
+            end: (end != start).then_some(end),
+        })
+    }
+}
+
+impl Default for RecordDate {
+    fn default() -> Self {
+        Self {
+            start: PartialDate::Day(NaiveDate::default()),
+            end: None,
+        }
+    }
+}
+
+impl TryFrom<RawDate> for RecordDate {
+    type Error = eyre::Error;
+
+    fn try_from(raw: RawDate) -> Result<Self, Self::Error> {
+        match raw {
+            RawDate::Year(year) => Self::parse(&year.to_string()),
+            RawDate::Text(text) => Self::parse(&text),
+        }
+    }
+}
+
+impl From<RecordDate> for String {
+    fn from(date: RecordDate) -> Self {
+        date.to_string()
+    }
+}
+
+impl Display for RecordDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&DateFormat::default().format(self))
+    }
+}
+
+/// Records are ordered by the first day and then by the last one,
+/// so a year goes before its months and days
+impl Ord for RecordDate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.first_day(), self.last_day()).cmp(&(other.first_day(), other.last_day()))
+    }
+}
+
+impl PartialOrd for RecordDate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Dates are equal when they cover the same days, as in their order
+impl PartialEq for RecordDate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RecordDate {}
+
+/// Period of records to build, open on either side
+#[derive(Debug, Clone, Default)]
+pub struct Period {
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+}
+
+impl Period {
+    /// Bounds are dates (a year or a month too) or periods back from today: `10d`, `3w`, `6m`, `2y`
+    pub fn new(since: Option<&str>, until: Option<&str>) -> eyre::Result<Self> {
+        let today = chrono::Local::now().date_naive();
+
+        let since = since
+            .map(|since| Self::parse_bound(since, today, PartialDate::first_day))
+            .transpose()?;
+
+        let until = until
+            .map(|until| Self::parse_bound(until, today, PartialDate::last_day))
+            .transpose()?;
+
+        if let (Some(since), Some(until)) = (since, until) {
+            if since > until {
+                return Err(eyre!("Period ends before it starts: {since} - {until}"));
+            }
+        }
+
+        Ok(Self { since, until })
+    }
+
+    fn parse_bound(
+        text: &str,
+        today: NaiveDate,
+        day: fn(&PartialDate) -> NaiveDate,
+    ) -> eyre::Result<NaiveDate> {
+        let text = text.trim();
+
+        let relative = text
+            .char_indices()
+            .last()
+            .and_then(|(index, unit)| Some((text[..index].parse::<u32>().ok()?, unit)));
+
+        let date = match relative {
+            Some((count, 'd')) => today.checked_sub_days(chrono::Days::new(count.into())),
+            Some((count, 'w')) => today.checked_sub_days(chrono::Days::new(u64::from(count) * 7)),
+            Some((count, 'm')) => today.checked_sub_months(chrono::Months::new(count)),
+            Some((count, 'y')) => today.checked_sub_months(chrono::Months::new(count * 12)),
+            _ => return Ok(day(&PartialDate::parse(text)?)),
+        };
+
+        date.ok_or_else(|| eyre!("Period is too long: {text}"))
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    /// Keeps records of the period and people having any of them
+    pub fn retain_records(&self, person_records: &mut PersonRecords) {
+        if self.is_bounded() {
+            Categorizer::retain_records(person_records, |rec| self.contains(&rec.date));
+        }
+    }
+
+    /// Record date overlaps with the period
+    pub fn contains(&self, date: &RecordDate) -> bool {
+        self.since.is_none_or(|since| date.last_day() >= since)
+            && self.until.is_none_or(|until| date.first_day() <= until)
+    }
+
+    /// Period as a heading: `Records from 2023.01.01 to 2023.12.31`
+    pub fn format(&self, date_format: &DateFormat) -> String {
+        let format = |date: NaiveDate| date_format.format_partial(&PartialDate::Day(date));
+
+        match (self.since, self.until) {
+            (Some(since), Some(until)) => {
+                format!("Records from {} to {}", format(since), format(until))
+            },
+            (Some(since), None) => format!("Records since {}", format(since)),
+            (None, Some(until)) => format!("Records until {}", format(until)),
+            (None, None) => "All records".to_string(),
+        }
+    }
+}
+
+/// Output format of record dates in labels and the table of contents
+#[derive(Debug, Clone)]
+pub struct DateFormat {
+    day: String,
+    month: String,
+}
+
+impl DateFormat {
+    pub const DEFAULT_DAY: &str = "%Y.%m.%d";
+    pub const DEFAULT_MONTH: &str = "%Y.%m";
+
+    pub fn new(day: &str, month: &str) -> eyre::Result<Self> {
+        // Invalid strftime items make chrono fail on formatting, so check them early
+        let sample = NaiveDate::default();
+
+        for format in [day, month] {
+            write!(String::new(), "{}", sample.format(format))
+                .map_err(|_| eyre!("Invalid date format: {format}"))?;
+        }
+
+        Ok(Self {
+            day: day.to_string(),
+            month: month.to_string(),
+        })
+    }
+
+    pub fn format(&self, date: &RecordDate) -> String {
+        match date.end {
+            Some(end) => format!(
+                "{} – {}",
+                self.format_partial(&date.start),
+                self.format_partial(&end)
+            ),
+            None => self.format_partial(&date.start),
+        }
+    }
+
+    pub fn format_partial(&self, date: &PartialDate) -> String {
+        match date {
+            PartialDate::Day(date) => date.format(&self.day).to_string(),
+            PartialDate::Month(date) => date.format(&self.month).to_string(),
+            PartialDate::Year(_) => date.first_day().year().to_string(),
+        }
+    }
+}
+
+impl Default for DateFormat {
+    fn default() -> Self {
+        Self {
+            day: Self::DEFAULT_DAY.to_string(),
+            month: Self::DEFAULT_MONTH.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn parses_partial_dates() {
+        for text in [
+            "2023.12.22",
+            "2023-12-22",
+            "2023/12/22",
+            "22.12.2023",
+            "22-12-2023",
+            "22/12/2023",
+        ] {
+            assert_eq!(
+                PartialDate::parse(text).unwrap(),
+                PartialDate::Day(day(2023, 12, 22)),
+                "{text}"
+            );
+        }
+
+        assert_eq!(
+            PartialDate::parse("2.1.2023").unwrap(),
+            PartialDate::Day(day(2023, 1, 2))
+        );
+
+        for text in [
+            "2023.12", "2023-12", "2023/12", "12.2023", "12-2023", "12/2023",
+        ] {
+            assert_eq!(
+                PartialDate::parse(text).unwrap(),
+                PartialDate::Month(day(2023, 12, 1)),
+                "{text}"
+            );
+        }
+
+        assert_eq!(
+            PartialDate::parse(" 2023 ").unwrap(),
+            PartialDate::Year(2023)
+        );
+    }
+
+    #[test]
+    fn rejects_dates_without_4_digit_year() {
+        for text in [
+            "22.12.23",
+            "23.12.22",
+            "12.23",
+            "01.02.03",
+            "023.12.22",
+            "20231.12.22",
+            "123",
+            "20231",
+            "2023.13",
+            "2023.02.30",
+            "2023.12.22.1",
+            "2023.12-22",
+            "december 2023",
+            "",
+        ] {
+            assert!(PartialDate::parse(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn parses_ranges() {
+        for text in [
+            "2023.12.01 - 2023.12.10",
+            "2023.12.01..2023.12.10",
+            "01.12.2023 – 10.12.2023",
+        ] {
+            let date = RecordDate::parse(text).unwrap();
+
+            assert_eq!(date.first_day(), day(2023, 12, 1), "{text}");
+            assert_eq!(date.last_day(), day(2023, 12, 10), "{text}");
+        }
+
+        let date = RecordDate::parse("2023.11..2024").unwrap();
+
+        assert_eq!(date.first_day(), day(2023, 11, 1));
+        assert_eq!(date.last_day(), day(2024, 12, 31));
+
+        assert!(RecordDate::parse("2023.12.10 - 2023.12.01").is_err());
+        assert!(RecordDate::parse("23.12.01 - 23.12.10").is_err());
+    }
+
+    #[test]
+    fn compares_dates_by_days() {
+        let month = RecordDate::parse("2023.12").unwrap();
+        let days = RecordDate::parse("2023.12.01 - 2023.12.31").unwrap();
+
+        assert_eq!(month, days);
+        assert_eq!(month.cmp(&days), Ordering::Equal);
+
+        let year = RecordDate::parse("2023").unwrap();
+
+        assert_ne!(year, month);
+        assert!(year < month);
+        assert!(month < RecordDate::parse("2023.12.02").unwrap());
+    }
+
+    #[test]
+    fn quotes_month_dates_of_yaml() {
+        let yaml = "date: 2023.10\nperson: maria\n  date: 10.2023 # month\n- date: 2023.10.01\n";
+
+        assert_eq!(
+            RecordDate::quote_yaml(yaml),
+            "date: \"2023.10\"\nperson: maria\n  date: \"10.2023\" # month\n- date: 2023.10.01\n"
+        );
+
+        let record: serde_yaml::Mapping =
+            serde_yaml::from_str(&RecordDate::quote_yaml("date: 2023.10")).unwrap();
+
+        assert_eq!(
+            RecordDate::deserialize(record["date"].clone())
+                .unwrap()
+                .first_day(),
+            day(2023, 10, 1)
+        );
+    }
+}
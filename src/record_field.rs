@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+
+use crate::categorizer::{Categorizer, PersonRecords};
+use crate::persons::Persons;
+use crate::structs::Record;
+
+/// Free-text record field to filter or group records by
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordField {
+    Doctor,
+    Place,
+}
+
+impl RecordField {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Doctor => "doctor",
+            Self::Place => "place",
+        }
+    }
+
+    pub fn value<'a>(&self, rec: &'a Record) -> Option<&'a str> {
+        match self {
+            Self::Doctor => rec.doctor.as_deref(),
+            Self::Place => rec.place.as_deref(),
+        }
+    }
+
+    /// Keeps records with the field containing any of the patterns (equal to with `exact`)
+    /// and people having any of them. Values are compared like person names
+    pub fn retain_records(
+        &self,
+        patterns: &[String],
+        exact: bool,
+        person_records: &mut PersonRecords,
+    ) {
+        if patterns.is_empty() {
+            return;
+        }
+
+        let patterns = patterns
+            .iter()
+            .map(|pattern| Persons::normalize(pattern))
+            .collect::<Vec<_>>();
+
+        Categorizer::retain_records(person_records, |rec| {
+            let Some(value) = self.value(rec).map(Persons::normalize) else {
+                return false;
+            };
+
+            patterns.iter().any(|pattern| {
+                if exact {
+                    value == *pattern
+                } else {
+                    value.contains(pattern.as_str())
+                }
+            })
+        });
+    }
+
+    /// Items grouped by the field of their records in alphabetical order,
+    /// items of records without the field are the last. Spelling variants
+    /// of a value share a section titled after the first one
+    pub fn sections<T>(
+        &self,
+        items: Vec<T>,
+        record: impl Fn(&T) -> &Record,
+    ) -> Vec<(String, Vec<T>)> {
+        let mut sections: BTreeMap<String, (String, Vec<T>)> = BTreeMap::new();
+        let mut missing = vec![];
+
+        for item in items {
+            match self.value(record(&item)) {
+                Some(value) => sections
+                    .entry(Persons::normalize(value))
+                    .or_insert_with(|| (value.trim().to_string(), vec![]))
+                    .1
+                    .push(item),
+                None => missing.push(item),
+            }
+        }
+
+        let mut sections = sections.into_values().collect::<Vec<_>>();
+
+        if !missing.is_empty() {
+            sections.push((format!("no {}", self.name()), missing));
+        }
+
+        sections
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::path::Path;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::structs::Message;
+
+/// Why a message didn't get into any record
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkipReason {
+    /// Leading code block looks like a record but fails to parse
+    BrokenRecord(String),
+    /// Attachment without text that doesn't follow or reply to a record
+    DetachedAttachment,
+    /// Attachment with text but without a record
+    AttachmentWithoutRecord,
+    /// Text message that isn't a part of a record
+    TextWithoutRecord,
+    /// Message or its record is excluded by overrides
+    Excluded,
+    /// Sticker, animation or file that can't be a part of a record
+    UnsupportedAttachment,
+}
+
+impl SkipReason {
+    pub fn summary(&self) -> &'static str {
+        match self {
+            Self::BrokenRecord(_) => "record metadata can't be parsed",
+            Self::DetachedAttachment => "attachment doesn't follow or reply to a record",
+            Self::AttachmentWithoutRecord => "attachment has text but no record",
+            Self::TextWithoutRecord => "text message isn't a part of a record",
+            Self::Excluded => "excluded by overrides",
+            Self::UnsupportedAttachment => "attachment type can't be a part of a record",
+        }
+    }
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BrokenRecord(err) => write!(f, "{}: {err}", self.summary()),
+            _ => f.write_str(self.summary()),
+        }
+    }
+}
+
+/// Message that didn't get into any record
+#[derive(Debug, Clone)]
+pub struct SkippedMessage {
+    pub message: Message,
+    pub reason: SkipReason,
+}
+
+#[derive(Serialize)]
+struct Row {
+    id: i64,
+    date: String,
+    from: String,
+    kind: String,
+    file: String,
+    link: String,
+    reason: String,
+}
+
+/// Report of skipped messages to go back to the chat and fix metadata
+pub struct Report;
+
+impl Report {
+    /// Writes a JSON report or a CSV one if the path has `.csv` extension
+    pub fn write(path: &Path, chat_id: i64, skipped: &[SkippedMessage]) -> eyre::Result<()> {
+        let rows = skipped.iter().map(|skipped| {
+            let msg = &skipped.message;
+
+            Row {
+                id: msg.id,
+                date: msg.date.to_string(),
+                from: msg.from.clone().unwrap_or_default(),
+                kind: Self::kind(msg).to_string(),
+                file: msg.photo.clone().or(msg.file.clone()).unwrap_or_default(),
+                link: msg.link(chat_id),
+                reason: skipped.reason.to_string(),
+            }
+        });
+
+        let file = File::create(path)?;
+
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+        {
+            let mut writer = csv::Writer::from_writer(file);
+
+            for row in rows {
+                writer.serialize(row)?;
+            }
+
+            writer.flush()?;
+        } else {
+            serde_json::to_writer_pretty(file, &rows.collect_vec())?;
+        }
+
+        Ok(())
+    }
+
+    /// Number of skipped messages of each reason
+    pub fn summary(skipped: &[SkippedMessage]) -> String {
+        skipped
+            .iter()
+            .counts_by(|skipped| skipped.reason.summary())
+            .into_iter()
+            .sorted_by_key(|(_, count)| *count)
+            .rev()
+            .map(|(summary, count)| format!("{count:>6} {summary}"))
+            .join("\n")
+    }
+
+    fn kind(msg: &Message) -> &'static str {
+        if msg.is_photo() {
+            "photo"
+        } else if msg.is_pdf() {
+            "pdf"
+        } else if msg.is_media() {
+            "media"
+        } else if msg.is_attachment() {
+            "file"
+        } else {
+            "text"
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+
+use eyre::eyre;
+
+/// Allowlist HTML sanitizer for user provided HTML (`html` blocks and tags)
+/// before it reaches wkhtmltopdf
+#[derive(Debug, Clone)]
+pub struct Sanitizer {
+    tags: HashSet<String>,
+    styles: HashSet<String>,
+}
+
+impl Sanitizer {
+    /// Tags removed together with their content, they can't be allowed
+    const CONTENT_TAGS: [&str; 2] = ["script", "style"];
+    pub const DEFAULT_STYLES: [&str; 7] = [
+        "color",
+        "background-color",
+        "font-weight",
+        "font-style",
+        "font-size",
+        "text-decoration",
+        "text-align",
+    ];
+    pub const DEFAULT_TAGS: [&str; 35] = [
+        "a",
+        "b",
+        "blockquote",
+        "br",
+        "code",
+        "del",
+        "div",
+        "em",
+        "font",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "hr",
+        "i",
+        "li",
+        "mark",
+        "ol",
+        "p",
+        "pre",
+        "s",
+        "small",
+        "span",
+        "strong",
+        "sub",
+        "sup",
+        "table",
+        "tbody",
+        "td",
+        "th",
+        "thead",
+        "tr",
+        "u",
+    ];
+
+    pub fn new(
+        tags: impl IntoIterator<Item = impl Into<String>>,
+        styles: impl IntoIterator<Item = impl Into<String>>,
+    ) -> eyre::Result<Self> {
+        let tags: HashSet<String> = tags.into_iter().map(Into::into).collect();
+
+        if let Some(tag) = tags
+            .iter()
+            .find(|tag| Self::CONTENT_TAGS.contains(&tag.to_lowercase().as_str()))
+        {
+            return Err(eyre!("--html-tags can't allow the {tag} tag"));
+        }
+
+        Ok(Self {
+            tags,
+            styles: styles.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    pub fn clean(&self, html: &str) -> String {
+        ammonia::Builder::default()
+            .clean_content_tags(Self::CONTENT_TAGS.into())
+            .tags(self.tags.iter().map(String::as_str).collect())
+            .add_generic_attributes(["class", "style"])
+            .add_tag_attributes("font", ["color"])
+            .filter_style_properties(self.styles.iter().map(String::as_str).collect())
+            .clean(html)
+            .to_string()
+    }
+
+    /// Escapes plain text to be put into HTML
+    pub fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+            .replace('\'', "&#39;")
+    }
+}
@@ -1,50 +1,173 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use chrono::NaiveDateTime;
 use csv::ReaderBuilder;
 use itertools::Itertools;
-use serde::{Deserialize, Serialize};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::record_date::RecordDate;
+use crate::sanitizer::Sanitizer;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum TextEntity {
-    Bold { text: String },
-    Code { text: String },
-    CustomEmoji { text: String, document_id: String },
-    Hashtag { text: String },
-    Italic { text: String },
-    Link { text: String },
-    Phone { text: String },
-    Plain { text: String },
-    Pre { text: String, language: String },
-    Strikethrough { text: String },
-    TextLink { text: String, href: String },
+    BankCard {
+        text: String,
+    },
+    Blockquote {
+        text: String,
+        #[serde(default)]
+        collapsed: bool,
+    },
+    Bold {
+        text: String,
+    },
+    BotCommand {
+        text: String,
+    },
+    Cashtag {
+        text: String,
+    },
+    Code {
+        text: String,
+    },
+    CustomEmoji {
+        text: String,
+        #[serde(default)]
+        document_id: String,
+    },
+    Email {
+        text: String,
+    },
+    Hashtag {
+        text: String,
+    },
+    Italic {
+        text: String,
+    },
+    Link {
+        text: String,
+    },
+    Mention {
+        text: String,
+    },
+    MentionName {
+        text: String,
+        #[serde(default)]
+        user_id: Option<i64>,
+    },
+    Phone {
+        text: String,
+    },
+    Plain {
+        text: String,
+    },
+    Pre {
+        text: String,
+        #[serde(default)]
+        language: String,
+    },
+    Spoiler {
+        text: String,
+    },
+    Strikethrough {
+        text: String,
+    },
+    TextLink {
+        text: String,
+        href: String,
+    },
+    Underline {
+        text: String,
+    },
+    /// Any entity type Telegram may add in the future
+    #[serde(untagged)]
+    Unknown {
+        #[serde(rename = "type")]
+        type_field: String,
+        #[serde(default)]
+        text: String,
+    },
 }
 
 impl TextEntity {
-    pub fn to_html(&self) -> String {
+    pub fn to_html(&self, sanitizer: &Sanitizer) -> String {
         let wrap = |text: &str, tag: &str| -> String {
-            let text = text.replace('\n', "<br />");
+            let text = Sanitizer::escape(text).replace('\n', "<br />");
             format!("<{tag}>{text}</{tag}>")
         };
 
         match self {
+            TextEntity::BankCard { text } => wrap(text, "span"),
+            TextEntity::Blockquote { text, .. } => {
+                format!(
+                    r#"<blockquote class="blockquote">{}</blockquote>"#,
+                    wrap(text, "span")
+                )
+            },
             TextEntity::Bold { text } => wrap(text, "b"),
+            TextEntity::BotCommand { text } => wrap(text, "code"),
+            TextEntity::Cashtag { text } => wrap(text, "b"),
             TextEntity::Code { text } => wrap(text, "code"),
             TextEntity::CustomEmoji { text, .. } => wrap(text, "span"),
-            TextEntity::Hashtag { text } => wrap(text, "bold"),
+            TextEntity::Email { text } => self.link_html(text),
+            TextEntity::Hashtag { text } => wrap(text, "b"),
             TextEntity::Italic { text } => wrap(text, "i"),
-            TextEntity::Link { text } => wrap(text, "span"),
+            TextEntity::Link { text } => self.link_html(text),
+            TextEntity::Mention { text } => wrap(text, "b"),
+            TextEntity::MentionName { text, .. } => wrap(text, "b"),
             TextEntity::Phone { text } => wrap(text, "span"),
             TextEntity::Plain { text } => wrap(text, "span"),
             TextEntity::Pre { text, language } => match language.as_str() {
-                "html" => text.to_string(),
+                "html" => sanitizer.clean(text),
                 "csv" => Self::csv_to_html(text),
                 "hidden" => String::new(),
                 _ => wrap(text, "pre"),
             },
+            // Spoilers are shown on paper, but marked
+            TextEntity::Spoiler { text } => {
+                format!(
+                    r#"<span style="background-color: #e9ecef">{}</span>"#,
+                    wrap(text, "span")
+                )
+            },
             TextEntity::Strikethrough { text } => wrap(text, "s"),
-            TextEntity::TextLink { text, href: _ } => wrap(text, "span"),
+            TextEntity::TextLink { text, .. } => self.link_html(text),
+            TextEntity::Underline { text } => wrap(text, "u"),
+            TextEntity::Unknown { type_field, text } => {
+                crate::write_err(format!(
+                    "warning: unknown text entity type `{type_field}` is rendered as plain text"
+                ))
+                .ok();
+
+                wrap(text, "span")
+            },
+        }
+    }
+
+    /// Url of link entities. Only web, email and Telegram links are taken
+    pub fn href(&self) -> Option<String> {
+        let href = match self {
+            TextEntity::Email { text } => format!("mailto:{text}"),
+            TextEntity::Link { text } if text.contains("://") => text.clone(),
+            TextEntity::Link { text } => format!("http://{text}"),
+            TextEntity::TextLink { href, .. } => href.clone(),
+            _ => return None,
+        };
+
+        LINK_SCHEMES
+            .iter()
+            .any(|scheme| href.starts_with(scheme))
+            .then_some(href)
+    }
+
+    fn link_html(&self, text: &str) -> String {
+        let text = Sanitizer::escape(text).replace('\n', "<br />");
+
+        match self.href() {
+            Some(href) => format!(r#"<a href="{}">{text}</a>"#, Sanitizer::escape(&href)),
+            None => format!("<span>{text}</span>"),
         }
     }
 
@@ -61,14 +184,14 @@ impl TextEntity {
                 Err(err) => {
                     crate::write_err(format!("{err:?}")).ok();
 
-                    return text.to_string();
+                    return format!("<pre>{}</pre>", Sanitizer::escape(text));
                 },
             };
 
             let tag = if i == 0 { "th" } else { "td" };
             let cells = record
                 .iter()
-                .map(|item| format!("<{tag}>{item}</{tag}>"))
+                .map(|item| format!("<{tag}>{}</{tag}>", Sanitizer::escape(item)))
                 .join("");
             if i == 0 {
                 header = format!("<tr>{cells}</tr>");
@@ -88,6 +211,77 @@ impl TextEntity {
     }
 }
 
+const LINK_SCHEMES: [&str; 4] = ["http://", "https://", "mailto:", "tg://"];
+
+/// Images `img2pdf` takes as is
+const IMAGE_MIME_TYPES: [&str; 2] = ["image/jpeg", "image/png"];
+
+/// Images to be converted to png before `img2pdf`
+const CONVERTIBLE_IMAGE_MIME_TYPES: [&str; 5] = [
+    "image/heic",
+    "image/heif",
+    "image/webp",
+    "image/tiff",
+    "image/gif",
+];
+
+/// Documents converted to pdf with LibreOffice
+const OFFICE_MIME_TYPES: [&str; 9] = [
+    "application/msword",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "application/vnd.oasis.opendocument.text",
+    "application/rtf",
+    "text/rtf",
+    "application/vnd.ms-excel",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "application/vnd.oasis.opendocument.spreadsheet",
+    "text/csv",
+];
+
+/// Telegram media types rendered as a placeholder page
+const MEDIA_TYPES: [&str; 5] = [
+    "voice_message",
+    "audio_file",
+    "video_file",
+    "video_message",
+    "animation",
+];
+
+/// Mime type of an attachment by its extension for sources that don't provide one
+pub fn guess_mime_type(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+
+    let mime_type = match extension.as_str() {
+        "pdf" => "application/pdf",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "heic" => "image/heic",
+        "heif" => "image/heif",
+        "webp" => "image/webp",
+        "tif" | "tiff" => "image/tiff",
+        "gif" => "image/gif",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "rtf" => "application/rtf",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+        "csv" => "text/csv",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        "webm" => "video/webm",
+        "mkv" => "video/x-matroska",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "wav" => "audio/wav",
+        _ => "application/octet-stream",
+    };
+
+    Some(mime_type.to_string())
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Export {
     pub id: i64,
@@ -95,6 +289,10 @@ pub struct Export {
     #[serde(rename = "type")]
     pub type_field: String,
     pub messages: Vec<Message>,
+
+    /// Records that are already assembled by the source (e.g. folder with sidecars)
+    #[serde(default)]
+    pub records: Vec<Record>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -110,6 +308,7 @@ pub struct Message {
     pub from_id: Option<String>,
     pub forwarded_from: Option<String>,
     pub reply_to_message_id: Option<i64>,
+    pub action: Option<String>,
     pub text_entities: Vec<TextEntity>,
     pub edited: Option<NaiveDateTime>,
     pub edited_unixtime: Option<String>,
@@ -123,20 +322,61 @@ pub struct Message {
     pub duration_seconds: Option<i64>,
 
     pub export_path: Option<PathBuf>,
+    /// Link to the original document if it's not a Telegram message
+    pub link: Option<String>,
+    /// Record message id the message is attached to by overrides
+    #[serde(skip)]
+    pub part_of: Option<i64>,
+    /// Excluded by overrides with its record and the record continuation
+    #[serde(skip)]
+    pub excluded: bool,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Record {
-    pub date: String,
+    pub date: RecordDate,
     pub tags: Vec<String>,
-    pub person: String,
+    /// One person or a list of people sharing the record
+    #[serde(deserialize_with = "deserialize_persons")]
+    pub person: Vec<String>,
     #[serde(default)]
     pub messages: Vec<Message>,
     pub doctor: Option<String>,
     pub place: Option<String>,
+    /// Number of following messages of the same sender the record consists of
+    #[serde(default)]
+    pub parts: Option<usize>,
+    /// Replies to the record with follow-up notes
+    #[serde(default)]
+    pub addenda: Vec<Message>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn deserialize_persons<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    let persons = match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(person) => vec![person],
+        OneOrMany::Many(persons) => persons,
+    };
+
+    if persons.is_empty() {
+        return Err(D::Error::custom("person list is empty"));
+    }
+
+    Ok(persons)
 }
 
 impl Record {
+    /// Fields of the record metadata block
+    pub const FIELDS: [&str; 6] = ["date", "person", "tags", "doctor", "place", "parts"];
+
     pub fn record_id(&self) -> String {
         self.messages
             .iter()
@@ -145,13 +385,25 @@ impl Record {
             .join("-")
     }
 
-    pub fn first_message_id(&self) -> i64 {
+    pub fn link(&self, chat_id: i64) -> String {
         self.messages
             .first()
-            .map(|message| message.id)
+            .map(|message| message.link(chat_id))
             .unwrap_or_default()
     }
 
+    pub fn link_text(&self) -> String {
+        self.messages
+            .first()
+            .map(|message| message.link_text())
+            .unwrap_or_default()
+    }
+
+    /// Record is in documents of several people
+    pub fn is_shared(&self) -> bool {
+        self.person.len() > 1
+    }
+
     pub fn is_images(&self) -> bool {
         self.messages.iter().any(|message| message.is_photo())
     }
@@ -163,9 +415,42 @@ impl Message {
     }
 
     pub fn is_photo(&self) -> bool {
-        self.photo.is_some()
-            || self.mime_type == Some("image/jpeg".into())
-            || self.mime_type == Some("image/png".into())
+        self.photo.is_some() || self.is_image_file() || self.needs_image_conversion()
+    }
+
+    /// Image attached as a file in a format `img2pdf` takes as is
+    fn is_image_file(&self) -> bool {
+        self.mime_type
+            .as_deref()
+            .is_some_and(|mime_type| IMAGE_MIME_TYPES.contains(&mime_type))
+    }
+
+    /// Image attached as a file in a format `img2pdf` can't take (HEIC, TIFF, etc.)
+    pub fn needs_image_conversion(&self) -> bool {
+        self.photo.is_none()
+            && self
+                .mime_type
+                .as_deref()
+                .is_some_and(|mime_type| CONVERTIBLE_IMAGE_MIME_TYPES.contains(&mime_type))
+    }
+
+    /// Multipage images are split to several pages
+    pub fn is_multipage_image(&self) -> bool {
+        self.needs_image_conversion() && self.mime_type.as_deref() == Some("image/tiff")
+    }
+
+    pub fn link(&self, chat_id: i64) -> String {
+        self.link
+            .clone()
+            .unwrap_or_else(|| format!("https://t.me/c/{chat_id}/{id}", id = self.id))
+    }
+
+    /// Message id for Telegram messages and file path for other sources
+    pub fn link_text(&self) -> String {
+        match (&self.link, &self.file) {
+            (Some(_), Some(file)) => file.clone(),
+            _ => self.id.to_string(),
+        }
     }
 
     pub fn unwrap_export_path(&self) -> PathBuf {
@@ -178,9 +463,7 @@ impl Message {
             export_path.push(photo);
 
             export_path
-        } else if self.mime_type == Some("image/jpeg".into())
-            || self.mime_type == Some("image/png".into())
-        {
+        } else if self.is_image_file() || self.needs_image_conversion() {
             self.unwrap_file()
         } else {
             panic!("File should exist")
@@ -199,6 +482,82 @@ impl Message {
         self.mime_type == Some("application/pdf".into())
     }
 
+    /// Message with any file or photo
+    pub fn is_attachment(&self) -> bool {
+        self.photo.is_some() || self.file.is_some()
+    }
+
+    /// Attachment that makes pages of a record: a document, a photo, a voice,
+    /// video or audio message, but not a sticker or an animation
+    pub fn is_record_part(&self) -> bool {
+        !matches!(self.media_type.as_deref(), Some("sticker" | "animation"))
+            && (self.is_pdf() || self.is_office_document() || self.is_photo() || self.is_media())
+    }
+
+    pub fn sender(&self) -> (Option<String>, Option<String>) {
+        (self.from_id.clone(), self.from.clone())
+    }
+
+    pub fn is_office_document(&self) -> bool {
+        self.mime_type
+            .as_deref()
+            .is_some_and(|mime_type| OFFICE_MIME_TYPES.contains(&mime_type))
+    }
+
+    /// Voice, video and audio messages
+    pub fn is_media(&self) -> bool {
+        self.media_type
+            .as_deref()
+            .is_some_and(|media_type| MEDIA_TYPES.contains(&media_type))
+            || self.mime_type.as_deref().is_some_and(|mime_type| {
+                mime_type.starts_with("video/") || mime_type.starts_with("audio/")
+            })
+    }
+
+    pub fn is_audio(&self) -> bool {
+        matches!(
+            self.media_type.as_deref(),
+            Some("voice_message" | "audio_file")
+        ) || self
+            .mime_type
+            .as_deref()
+            .is_some_and(|mime_type| mime_type.starts_with("audio/"))
+    }
+
+    pub fn is_video(&self) -> bool {
+        self.mime_type
+            .as_deref()
+            .is_some_and(|mime_type| mime_type.starts_with("video/"))
+    }
+
+    pub fn unwrap_thumbnail(&self) -> Option<PathBuf> {
+        let mut export_path = self.unwrap_export_path();
+        export_path.push(self.thumbnail.as_ref()?);
+
+        Some(export_path)
+    }
+
+    /// Text entities of the message without the record block
+    pub fn caption_entities(&self) -> &[TextEntity] {
+        if self.has_record() {
+            &self.text_entities[1..]
+        } else {
+            &self.text_entities
+        }
+    }
+
+    /// Code block with record metadata that fails to parse (e.g. a typo in the date)
+    pub fn has_broken_record(&self) -> bool {
+        let Some(TextEntity::Pre { text, .. }) = self.text_entities.first() else {
+            return false;
+        };
+
+        let is_metadata = serde_yaml::from_str::<serde_yaml::Mapping>(text)
+            .is_ok_and(|mapping| mapping.contains_key("date") && mapping.contains_key("person"));
+
+        is_metadata && !self.has_record()
+    }
+
     pub fn has_record(&self) -> bool {
         let descrition = self.get_record();
 
@@ -210,10 +569,7 @@ impl Message {
             return Err(eyre::eyre!("No entry"));
         };
 
-        // let reg = Regex::new(r"(?m)^date: (\S*)$")?;
-        // let text = reg.replace(text, r#"date: "$1""#);
-
-        let entry = serde_yaml::from_str(text);
+        let entry = serde_yaml::from_str(&RecordDate::quote_yaml(text));
 
         match entry {
             Ok(expr) => Ok(expr),
@@ -0,0 +1,262 @@
+use std::str::FromStr;
+
+use itertools::Itertools;
+
+use crate::categorizer::{Categorizer, PersonRecords};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+/// Boolean expression over record tags:
+/// `cardiology and not dental`, `ecg | holter`, `(ecg or holter) & "blood test"`.
+///
+/// Tags are compared as whole tags after HTML stripping and case folding
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagQuery {
+    Tag(String),
+    Not(Box<TagQuery>),
+    And(Box<TagQuery>, Box<TagQuery>),
+    Or(Box<TagQuery>, Box<TagQuery>),
+}
+
+impl TagQuery {
+    pub fn matches(&self, tags: &[String]) -> bool {
+        match self {
+            Self::Tag(tag) => tags.iter().any(|other| Self::fold(other) == *tag),
+            Self::Not(query) => !query.matches(tags),
+            Self::And(left, right) => left.matches(tags) && right.matches(tags),
+            Self::Or(left, right) => left.matches(tags) || right.matches(tags),
+        }
+    }
+
+    /// Keeps records matching the query and people having any of them
+    pub fn retain_records(&self, person_records: &mut PersonRecords) {
+        Categorizer::retain_records(person_records, |rec| self.matches(&rec.tags));
+    }
+
+    fn fold(tag: &str) -> String {
+        crate::strip_html_tags(tag)
+            .split_whitespace()
+            .join(" ")
+            .to_lowercase()
+    }
+
+    fn tokenize(query: &str) -> Result<Vec<Token>, String> {
+        let mut tokens = vec![];
+        let mut chars = query.chars().peekable();
+
+        while let Some(char) = chars.next() {
+            let token = match char {
+                _ if char.is_whitespace() => continue,
+                '(' => Token::Open,
+                ')' => Token::Close,
+                '|' => Token::Or,
+                '&' => Token::And,
+                '!' => Token::Not,
+                '"' | '\'' => {
+                    let mut tag = String::new();
+
+                    loop {
+                        match chars.next() {
+                            Some(next) if next == char => break,
+                            Some(next) => tag.push(next),
+                            None => return Err("unterminated quote in the tag query".into()),
+                        }
+                    }
+
+                    Token::Tag(Self::fold(&tag))
+                },
+                _ => {
+                    let mut word = char.to_string();
+
+                    while let Some(next) = chars.next_if(|next| !Self::is_delimiter(*next)) {
+                        word.push(next);
+                    }
+
+                    match word.to_lowercase().as_str() {
+                        "and" => Token::And,
+                        "or" => Token::Or,
+                        "not" => Token::Not,
+                        _ => Token::Tag(Self::fold(&word)),
+                    }
+                },
+            };
+
+            tokens.push(token);
+        }
+
+        Ok(tokens)
+    }
+
+    fn is_delimiter(char: char) -> bool {
+        char.is_whitespace() || "()|&!\"'".contains(char)
+    }
+
+    /// or := and (`or` and)*
+    fn parse_or(tokens: &[Token], pos: &mut usize) -> Result<Self, String> {
+        let mut query = Self::parse_and(tokens, pos)?;
+
+        while tokens.get(*pos) == Some(&Token::Or) {
+            *pos += 1;
+
+            query = Self::Or(Box::new(query), Box::new(Self::parse_and(tokens, pos)?));
+        }
+
+        Ok(query)
+    }
+
+    /// and := not (`and` not)*
+    fn parse_and(tokens: &[Token], pos: &mut usize) -> Result<Self, String> {
+        let mut query = Self::parse_not(tokens, pos)?;
+
+        while tokens.get(*pos) == Some(&Token::And) {
+            *pos += 1;
+
+            query = Self::And(Box::new(query), Box::new(Self::parse_not(tokens, pos)?));
+        }
+
+        Ok(query)
+    }
+
+    /// not := `not` not | `(` or `)` | tag
+    fn parse_not(tokens: &[Token], pos: &mut usize) -> Result<Self, String> {
+        let token = tokens
+            .get(*pos)
+            .ok_or_else(|| "unexpected end of the tag query".to_string())?;
+
+        *pos += 1;
+
+        match token {
+            Token::Not => Ok(Self::Not(Box::new(Self::parse_not(tokens, pos)?))),
+            Token::Open => {
+                let query = Self::parse_or(tokens, pos)?;
+
+                if tokens.get(*pos) != Some(&Token::Close) {
+                    return Err("missing `)` in the tag query".into());
+                }
+
+                *pos += 1;
+
+                Ok(query)
+            },
+            Token::Tag(tag) => Ok(Self::Tag(tag.clone())),
+            token => Err(format!("unexpected {token:?} in the tag query")),
+        }
+    }
+}
+
+impl FromStr for TagQuery {
+    type Err = String;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        let tokens = Self::tokenize(query)?;
+        let mut pos = 0;
+
+        let query = Self::parse_or(&tokens, &mut pos)?;
+
+        if let Some(token) = tokens.get(pos) {
+            return Err(format!("unexpected {token:?} in the tag query"));
+        }
+
+        Ok(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(tag: &str) -> TagQuery {
+        TagQuery::Tag(tag.to_string())
+    }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn parses_negation() {
+        let query: TagQuery = "Cardiology and not dental".parse().unwrap();
+
+        assert_eq!(
+            query,
+            TagQuery::And(
+                Box::new(tag("cardiology")),
+                Box::new(TagQuery::Not(Box::new(tag("dental"))))
+            )
+        );
+
+        assert!(query.matches(&tags(&["cardiology", "ECG"])));
+        assert!(!query.matches(&tags(&["cardiology", "<b>Dental</b>"])));
+        assert_eq!("cardiology & !dental".parse(), Ok(query));
+    }
+
+    #[test]
+    fn binds_and_tighter_than_or() {
+        let query: TagQuery = "a or b and c".parse().unwrap();
+
+        assert_eq!(
+            query,
+            TagQuery::Or(
+                Box::new(tag("a")),
+                Box::new(TagQuery::And(Box::new(tag("b")), Box::new(tag("c"))))
+            )
+        );
+
+        assert!(query.matches(&tags(&["a"])));
+        assert!(!query.matches(&tags(&["b"])));
+        assert!(query.matches(&tags(&["b", "c"])));
+    }
+
+    #[test]
+    fn parses_nested_parentheses() {
+        let query: TagQuery = r#"((ecg | holter) & ("blood  test" or not mri))"#.parse().unwrap();
+
+        assert_eq!(
+            query,
+            TagQuery::And(
+                Box::new(TagQuery::Or(Box::new(tag("ecg")), Box::new(tag("holter")))),
+                Box::new(TagQuery::Or(
+                    Box::new(tag("blood test")),
+                    Box::new(TagQuery::Not(Box::new(tag("mri"))))
+                ))
+            )
+        );
+
+        assert!(query.matches(&tags(&["holter", "Blood test", "mri"])));
+        assert!(!query.matches(&tags(&["ecg", "mri"])));
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        for query in [
+            "(ecg or holter",
+            "ecg)",
+            "",
+            "   ",
+            "ecg and",
+            "ecg or",
+            "not",
+            "ecg and or holter",
+            "\"blood test",
+        ] {
+            assert!(query.parse::<TagQuery>().is_err(), "{query}");
+        }
+
+        assert_eq!(
+            "(ecg or holter".parse::<TagQuery>(),
+            Err("missing `)` in the tag query".to_string())
+        );
+        assert_eq!(
+            "".parse::<TagQuery>(),
+            Err("unexpected end of the tag query".to_string())
+        );
+    }
+}
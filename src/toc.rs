@@ -1,83 +1,164 @@
 use itertools::Itertools;
 
+use crate::record_date::DateFormat;
+use crate::sanitizer::Sanitizer;
 use crate::structs::Record;
 
+#[derive(Clone)]
 pub struct TocItem<'a> {
-    pub pages: u8,
+    pub pages: usize,
     pub record: &'a Record,
 }
 
+/// Records preceded by a heading page if the section has a title
+pub struct TocSection<'a> {
+    pub title: Option<String>,
+    /// Pages of the heading, a single page until the heading is generated
+    pub heading_pages: usize,
+    pub items: Vec<TocItem<'a>>,
+}
+
 pub struct Toc<'a> {
     pub chat_id: i64,
-    pub items: Vec<TocItem<'a>>,
+    pub sections: Vec<TocSection<'a>>,
+    /// Covered period if records are filtered by date
+    pub period: Option<String>,
+    /// Volume name if the person document is split
+    pub volume: Option<String>,
 }
 
 impl<'a> Toc<'a> {
     pub fn new(chat_id: i64) -> Self {
-        Self::new_from(chat_id, Vec::new())
-    }
-
-    pub fn new_from(chat_id: i64, toc_items: impl IntoIterator<Item = TocItem<'a>>) -> Self {
         Self {
             chat_id,
-            items: toc_items.into_iter().collect(),
+            sections: Vec::new(),
+            period: None,
+            volume: None,
         }
     }
 
     pub fn append(&mut self, toc_items: impl IntoIterator<Item = TocItem<'a>>) {
-        self.items.extend(toc_items);
+        self.append_section(None, toc_items);
     }
 
-    pub fn generate_html(&self, shift: u8) -> String {
+    pub fn append_section(
+        &mut self,
+        title: Option<String>,
+        toc_items: impl IntoIterator<Item = TocItem<'a>>,
+    ) {
+        self.sections.push(TocSection {
+            heading_pages: usize::from(title.is_some()),
+            title,
+            items: toc_items.into_iter().collect(),
+        });
+    }
+
+    pub fn generate_html(
+        &self,
+        shift: usize,
+        sanitizer: &Sanitizer,
+        date_format: &DateFormat,
+    ) -> String {
         let mut current_page = shift;
-        let content = self
-            .items
-            .iter()
-            .enumerate()
-            .map(|(index, item)| {
+        let mut index = 0;
+        let mut content = String::new();
+
+        for section in &self.sections {
+            if let Some(title) = &section.title {
+                let page = current_page + 1;
+
+                current_page += section.heading_pages;
+
+                content += &format!(
+                    r#"
+                        <tr class="thead-light">
+                            <th colspan="3" style="text-align: left">{title}</th>
+                            <th style="text-align: right">{page}</th>
+                        </tr>
+                    "#,
+                    title = Sanitizer::escape(title),
+                );
+            }
+
+            for item in &section.items {
+                index += 1;
                 current_page += item.pages;
-                format!(
+
+                content += &format!(
                     r#"
                         <tr>
                             <td>{index}</td>
-                            <td>{date}<div class="message-id"><a href="https://t.me/c/{chat_id}/{id}">{id}</div></td>
+                            <td>{date}<div class="message-id"><a href="{link}">{link_text}</div></td>
                             <td style="width: 100%">
                                 {place}
                                 <ul><li>{tags}</li></ul>
                                 {doctor}
+                                {addenda}
+                                {shared}
                             </td>
                             <td style="text-align: right">{page}</td>
                         </tr>
                     "#,
-                    index = index + 1,
-                    chat_id = self.chat_id,
-                    id = item
+                    link = Sanitizer::escape(&item.record.link(self.chat_id)),
+                    link_text = Sanitizer::escape(&item.record.link_text()),
+                    date = Sanitizer::escape(&date_format.format(&item.record.date)),
+                    tags = item
                         .record
-                        .messages
-                        .first()
-                        .map(|message| message.id)
-                        .unwrap_or_default(),
-                    date = item.record.date,
-                    tags = item.record.tags.join("</li><li>"),
+                        .tags
+                        .iter()
+                        .map(|tag| sanitizer.clean(tag))
+                        .join("</li><li>"),
                     place = item
                         .record
                         .place
                         .as_ref()
-                        .map(|place| format!("<div class='small-font'>{place}</div>"))
+                        .map(|place| {
+                            format!("<div class='small-font'>{}</div>", Sanitizer::escape(place))
+                        })
                         .unwrap_or_default(),
                     doctor = item
                         .record
                         .doctor
                         .as_ref()
-                        .map(|doctor| format!("<div class='small-font'>{doctor}</div>"))
+                        .map(|doctor| {
+                            format!(
+                                "<div class='small-font'>{}</div>",
+                                Sanitizer::escape(doctor)
+                            )
+                        })
                         .unwrap_or_default(),
+                    addenda = match item.record.addenda.len() {
+                        0 => String::new(),
+                        count => format!("<div class='small-font'>comments: {count}</div>"),
+                    },
+                    shared = if item.record.is_shared() {
+                        format!(
+                            "<div class='small-font'>shared: {}</div>",
+                            Sanitizer::escape(&item.record.person.join(", "))
+                        )
+                    } else {
+                        String::new()
+                    },
                     page = current_page - item.pages + 1,
-                )
-            })
-            .join("");
+                );
+            }
+        }
+
+        let header = [
+            self.volume
+                .as_ref()
+                .map(|volume| format!("<h5>Volume {}</h5>", Sanitizer::escape(volume))),
+            self.period
+                .as_ref()
+                .map(|period| format!("<h5>{}</h5>", Sanitizer::escape(period))),
+        ]
+        .into_iter()
+        .flatten()
+        .join("");
 
         format!(
             r#"
+            {header}
             <table class="table table-striped table-sm">
                 <tr class="thead-dark">
                     <th style="text-align: left">#</th>
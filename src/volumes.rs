@@ -0,0 +1,397 @@
+use std::collections::{BTreeMap, HashSet};
+use std::mem;
+use std::path::PathBuf;
+
+use chrono::Datelike;
+use eyre::eyre;
+
+use crate::Cli;
+use crate::persons::Persons;
+use crate::record_date::{DateFormat, RecordDate};
+use crate::record_field::RecordField;
+use crate::sanitizer::Sanitizer;
+use crate::structs::Record;
+use crate::toc::TocItem;
+
+/// Record pdf with its size and table of contents item
+#[derive(Clone)]
+pub struct RecordPdf<'a> {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub item: TocItem<'a>,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeSplit {
+    /// Calendar year of the record start
+    Year,
+    /// The first tag of the record
+    Tag,
+}
+
+/// Volume of a person document with its own table of contents
+pub struct Volume<'a> {
+    pub name: String,
+    pub records: Vec<RecordPdf<'a>>,
+}
+
+/// Built volume listed in the index document
+pub struct VolumeSummary<'a> {
+    pub file_name: String,
+    pub records: Vec<&'a Record>,
+    pub pages: usize,
+}
+
+/// Split of person documents into volumes by year or tag and by page or size limits
+#[derive(Debug, Clone)]
+pub struct Volumes {
+    split: Option<VolumeSplit>,
+    max_pages: Option<usize>,
+    max_bytes: Option<u64>,
+    /// Sections add heading pages to volumes
+    group_by: Option<RecordField>,
+}
+
+impl Volumes {
+    pub fn new(cli: &Cli) -> eyre::Result<Self> {
+        if cli.max_pages == Some(0) {
+            return Err(eyre!("--max-pages must be positive"));
+        }
+
+        let max_bytes = match cli.max_mb {
+            Some(max_mb) if max_mb.is_nan() || max_mb <= 0.0 => {
+                return Err(eyre!("--max-mb must be positive"));
+            },
+            Some(max_mb) => Some((max_mb * 1024.0 * 1024.0) as u64),
+            None => None,
+        };
+
+        Ok(Self {
+            split: cli.split_by,
+            max_pages: cli.max_pages,
+            max_bytes,
+            group_by: cli.group_by,
+        })
+    }
+
+    pub fn max_pages(&self) -> Option<usize> {
+        self.max_pages
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.split.is_some() || self.max_pages.is_some() || self.max_bytes.is_some()
+    }
+
+    /// Volumes in the order of years or tags, records keep their order inside.
+    /// Limits start a new volume before the record exceeding them. The page limit
+    /// counts section heading pages and `toc_pages` of the table of contents,
+    /// so only a record larger than the limit makes a volume exceed it
+    pub fn split<'a>(&self, records: Vec<RecordPdf<'a>>, toc_pages: usize) -> Vec<Volume<'a>> {
+        let mut volumes = vec![];
+
+        for (key, records) in self.groups(records, |record| record.item.record) {
+            let parts = self.limit(records, toc_pages);
+            let numbered = parts.len() > 1;
+
+            for (index, records) in parts.into_iter().enumerate() {
+                let number = (index + 1).to_string();
+
+                let name = match &key {
+                    Some(key) if numbered => format!("{key}-{number}"),
+                    Some(key) => key.clone(),
+                    None => number,
+                };
+
+                volumes.push(Volume { name, records });
+            }
+        }
+
+        volumes
+    }
+
+    /// Every name volumes of the records can get, to check them before building
+    pub fn possible_names(&self, records: &[Record]) -> Vec<String> {
+        let has_limits = self.max_pages.is_some() || self.max_bytes.is_some();
+
+        self.groups(records.iter().collect(), |rec| rec)
+            .into_iter()
+            .flat_map(|(key, records)| {
+                let numbers = (1..=records.len()).filter(move |_| has_limits);
+
+                match key {
+                    Some(key) => [key.clone()]
+                        .into_iter()
+                        .chain(numbers.map(|number| format!("{key}-{number}")))
+                        .collect::<Vec<_>>(),
+                    None => (1..=records.len())
+                        .map(|number| number.to_string())
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Items by year or tag of their records in alphabetical order (a single group without a split)
+    fn groups<'a, T>(
+        &self,
+        items: Vec<T>,
+        record: impl Fn(&T) -> &'a Record,
+    ) -> Vec<(Option<String>, Vec<T>)> {
+        let Some(split) = self.split else {
+            return vec![(None, items)];
+        };
+
+        let mut groups: BTreeMap<String, (String, Vec<T>)> = BTreeMap::new();
+
+        for item in items {
+            let rec = record(&item);
+
+            let key = match split {
+                VolumeSplit::Year => rec.date.first_day().year().to_string(),
+                VolumeSplit::Tag => rec
+                    .tags
+                    .first()
+                    .map(|tag| tag.trim().to_string())
+                    .unwrap_or_else(|| "untagged".to_string()),
+            };
+
+            groups
+                .entry(Persons::normalize(&key))
+                .or_insert_with(|| (key, vec![]))
+                .1
+                .push(item);
+        }
+
+        groups
+            .into_values()
+            .map(|(key, records)| (Some(key), records))
+            .collect()
+    }
+
+    fn limit<'a>(&self, records: Vec<RecordPdf<'a>>, toc_pages: usize) -> Vec<Vec<RecordPdf<'a>>> {
+        let mut parts = vec![];
+        let mut part = vec![];
+        let mut pages = toc_pages;
+        let mut bytes = 0;
+        let mut sections = HashSet::new();
+
+        for record in records {
+            let section = self
+                .group_by
+                .map(|field| field.value(record.item.record).map(Persons::normalize));
+
+            // The first record of a section brings its heading page. Headings aren't
+            // generated yet, so a heading is taken for a single page like an adaptive
+            // one, a longer one with `--unadaptive-text-pages` isn't counted
+            let heading_pages = usize::from(section.is_some() && !sections.contains(&section));
+
+            let exceeds = self
+                .max_pages
+                .is_some_and(|max| pages + heading_pages + record.item.pages > max)
+                || self.max_bytes.is_some_and(|max| bytes + record.bytes > max);
+
+            if exceeds && !part.is_empty() {
+                parts.push(mem::take(&mut part));
+                pages = toc_pages;
+                bytes = 0;
+                sections.clear();
+            }
+
+            pages += usize::from(section.is_some() && sections.insert(section)) + record.item.pages;
+            bytes += record.bytes;
+            part.push(record);
+        }
+
+        if !part.is_empty() {
+            parts.push(part);
+        }
+
+        parts
+    }
+
+    /// Index document listing volumes with their numbers of records and dates
+    pub fn index_html(person: &str, volumes: &[VolumeSummary], date_format: &DateFormat) -> String {
+        let content = volumes
+            .iter()
+            .enumerate()
+            .map(|(index, volume)| {
+                let dates = RecordDate::span(volume.records.iter().map(|rec| &rec.date))
+                    .map(|span| date_format.format(&span))
+                    .unwrap_or_default();
+
+                format!(
+                    r#"
+                        <tr>
+                            <td>{number}</td>
+                            <td><a href="{file_name}">{file_name}</a></td>
+                            <td>{records}</td>
+                            <td>{dates}</td>
+                            <td style="text-align: right">{pages}</td>
+                        </tr>
+                    "#,
+                    number = index + 1,
+                    file_name = Sanitizer::escape(&volume.file_name),
+                    records = volume.records.len(),
+                    dates = Sanitizer::escape(&dates),
+                    pages = volume.pages,
+                )
+            })
+            .collect::<String>();
+
+        format!(
+            r#"
+            <h5>Volumes of {person}</h5>
+            <table class="table table-striped table-sm">
+                <tr class="thead-dark">
+                    <th style="text-align: left">#</th>
+                    <th style="text-align: left">file</th>
+                    <th style="text-align: left">records</th>
+                    <th style="text-align: left">dates</th>
+                    <th style="text-align: right">pages</th>
+                </tr>
+                {content}
+            </table>
+            "#,
+            person = Sanitizer::escape(person),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(date: &str, tag: &str, doctor: &str) -> Record {
+        Record {
+            date: RecordDate::parse(date).unwrap(),
+            tags: vec![tag.to_string()],
+            doctor: Some(doctor.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn pdfs(records: &[Record], pages: usize, bytes: u64) -> Vec<RecordPdf<'_>> {
+        records
+            .iter()
+            .map(|record| RecordPdf {
+                path: PathBuf::new(),
+                bytes,
+                item: TocItem { pages, record },
+            })
+            .collect()
+    }
+
+    fn volumes(split: Option<VolumeSplit>, max_pages: Option<usize>) -> Volumes {
+        Volumes {
+            split,
+            max_pages,
+            max_bytes: None,
+            group_by: None,
+        }
+    }
+
+    fn names(volumes: &[Volume]) -> Vec<String> {
+        volumes.iter().map(|volume| volume.name.clone()).collect()
+    }
+
+    fn sizes(volumes: &[Volume]) -> Vec<usize> {
+        volumes.iter().map(|volume| volume.records.len()).collect()
+    }
+
+    #[test]
+    fn splits_by_pages_with_toc_pages() {
+        let records = vec![record("2023", "ecg", "smith"); 5];
+
+        let split = volumes(None, Some(10)).split(pdfs(&records, 3, 0), 1);
+
+        assert_eq!(names(&split), ["1", "2"]);
+        assert_eq!(sizes(&split), [3, 2]);
+
+        let split = volumes(None, Some(10)).split(pdfs(&records, 3, 0), 2);
+
+        assert_eq!(sizes(&split), [2, 2, 1]);
+    }
+
+    #[test]
+    fn counts_section_heading_pages() {
+        let records = [
+            record("2023", "ecg", "smith"),
+            record("2023", "ecg", "Smith "),
+            record("2023", "ecg", "jones"),
+            record("2023", "ecg", "jones"),
+        ];
+
+        let mut volumes = volumes(None, Some(7));
+        volumes.group_by = Some(RecordField::Doctor);
+
+        // 1 toc + 1 heading + 2 + 2, the next heading and record don't fit
+        assert_eq!(sizes(&volumes.split(pdfs(&records, 2, 0), 1)), [2, 2]);
+    }
+
+    #[test]
+    fn splits_by_bytes() {
+        let records = vec![record("2023", "ecg", "smith"); 5];
+
+        let mut volumes = volumes(None, None);
+        volumes.max_bytes = Some(1000);
+
+        assert_eq!(
+            sizes(&volumes.split(pdfs(&records, 100, 400), 1)),
+            [2, 2, 1]
+        );
+    }
+
+    #[test]
+    fn keeps_oversize_record_in_own_volume() {
+        let records = vec![record("2023", "ecg", "smith"); 3];
+
+        let mut records = pdfs(&records, 2, 0);
+        records[1].item.pages = 20;
+
+        assert_eq!(sizes(&volumes(None, Some(10)).split(records, 1)), [1, 1, 1]);
+    }
+
+    #[test]
+    fn numbers_only_split_groups() {
+        let records = [
+            record("2023", "Cardiology", "smith"),
+            record("2023", "dental", "smith"),
+            record("2023", " cardiology", "smith"),
+            record("2023", "cardiology", "smith"),
+        ];
+
+        let split = volumes(Some(VolumeSplit::Tag), Some(5)).split(pdfs(&records, 2, 0), 1);
+
+        assert_eq!(names(&split), ["Cardiology-1", "Cardiology-2", "dental"]);
+        assert_eq!(sizes(&split), [2, 1, 1]);
+
+        let split = volumes(Some(VolumeSplit::Year), None).split(pdfs(&records, 2, 0), 1);
+
+        assert_eq!(names(&split), ["2023"]);
+    }
+
+    #[test]
+    fn possible_names_include_split_names() {
+        let records = [
+            record("2022.05", "ecg", "smith"),
+            record("2023.01.02", "Dental", "smith"),
+            record("2023.03", "ecg", "smith"),
+            record("2023", "dental", "smith"),
+            record("2023.12.01 - 2024.01.10", "ecg", "smith"),
+        ];
+
+        for split in [None, Some(VolumeSplit::Year), Some(VolumeSplit::Tag)] {
+            for max_pages in [None, Some(1), Some(5), Some(100)] {
+                let volumes = volumes(split, max_pages);
+                let possible = volumes.possible_names(&records);
+
+                for name in names(&volumes.split(pdfs(&records, 2, 0), 1)) {
+                    assert!(
+                        possible.contains(&name),
+                        "{name} of {split:?} and {max_pages:?}"
+                    );
+                }
+            }
+        }
+    }
+}